@@ -1,9 +1,19 @@
 use dependency::Dependency;
-use path_utils::ensure_dir;
-use vcs::load_vcs_info;
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+use serde_json;
+use consts::SOURCE_STATE_DIR;
+use path_utils::{copy_dir, ensure_dir, remove_dir};
+use vcs::{load_vcs_for, VcsCommandList};
+use hooks::{plan_hook, run_hooks};
+use patches::{revert_patches, update_patches};
+use vendor::is_vendored;
+use events::Event;
 use error::DerpyError;
 use log::Log;
 
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum AcquireOutcome {
     Acquired {
         at_version: String,
@@ -24,7 +34,151 @@ pub enum AcquireOutcome {
     },
 }
 
-#[derive(Debug)]
+/// Something acquiring a dependency would do, worked out without doing it. `commands` are `None`
+/// where derpy does the work itself rather than running commands.
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlannedAction {
+    Acquire {
+        commands: Option<VcsCommandList>,
+    },
+    Checkout {
+        to_version: String,
+        commands: Option<VcsCommandList>,
+    },
+    Upgrade {
+        commands: Option<VcsCommandList>,
+    },
+    ApplyPatches {
+        patches: Vec<String>,
+    },
+    /// hooks after an upgrade only run if it changes the revision, and `{DEP_REVISION}` is left as
+    /// it is when the revision isn't known until the VCS commands have run
+    RunHook {
+        hook: String,
+        commands: VcsCommandList,
+    },
+}
+
+/// What `acquire` would do to a dependency.
+pub struct AcquirePlan {
+    pub actions: Vec<PlannedAction>,
+    /// the revision it would be at afterwards, `None` if that isn't known until the VCS commands run
+    pub revision: Option<String>,
+}
+
+/// Where a dependency's checkout was acquired from, kept in `.derpy/sources/<name>.json` so that a
+/// checkout left behind by a different url or VCS is noticed, and so that a directory derpy didn't
+/// make isn't mistaken for one of its own.
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct CheckoutSource {
+    pub vcs: String,
+    pub url: String,
+}
+
+impl CheckoutSource {
+    pub fn of(dep: &Dependency) -> Self {
+        Self {
+            vcs: dep.vcs.clone(),
+            url: dep.url.clone(),
+        }
+    }
+}
+
+fn source_file(root: &Path, dep: &Dependency) -> PathBuf {
+    root.join(SOURCE_STATE_DIR).join(format!("{}.json", dep.name))
+}
+
+/// where the dependency's checkout was acquired from, if derpy acquired it
+pub fn load_source(root: &Path, dep: &Dependency) -> Result<Option<CheckoutSource>, DerpyError> {
+    let path = source_file(root, dep);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let contents = match read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => return Err(DerpyError::UnableToReadCheckoutSource {
+            path,
+            error: e,
+        }),
+    };
+    match serde_json::from_str(&contents) {
+        Ok(source) => Ok(Some(source)),
+        Err(e) => Err(DerpyError::UnableToDecodeCheckoutSource {
+            path,
+            error: e,
+        }),
+    }
+}
+
+fn save_source(root: &Path, dep: &Dependency) -> Result<(), DerpyError> {
+    let path = source_file(root, dep);
+    if let Some(parent) = path.parent() {
+        ensure_dir(parent)?;
+    }
+    let contents = serde_json::to_string_pretty(&CheckoutSource::of(dep))
+        .expect("checkout sources are always representable as json");
+    if let Err(e) = write(&path, contents) {
+        return Err(DerpyError::UnableToWriteCheckoutSource {
+            path,
+            error: e,
+        });
+    }
+    Ok(())
+}
+
+/// Deletes a dependency's checkout if it was acquired from a different url or VCS to the one the
+/// dependency now wants, so that it is acquired afresh rather than updated from the wrong place.
+/// `previous` is where it came from if that wasn't recorded. A checkout with local modifications
+/// is left alone with an error, and one that can't be checked for them is left to its VCS.
+pub fn replace_moved_checkout(log: &Log, root: &Path, dep: &Dependency, previous: Option<CheckoutSource>, offline: bool, dry_run: bool) -> Result<(), DerpyError> {
+    let checkout_dir = dep.get_checkout_dir_in(root);
+    // path dependencies refuse to replace anything in their way themselves
+    if dep.vcs == "path" || is_vendored(root, dep) || !checkout_dir.is_dir() {
+        return Ok(());
+    }
+    let source = match load_source(root, dep)?.or(previous) {
+        Some(ref source) if *source == CheckoutSource::of(dep) => return Ok(()),
+        Some(source) => source,
+        None => return Ok(()),
+    };
+    if dry_run {
+        log.info(format!("would replace the checkout of '{}' from {}", dep.name, source.url));
+        return Ok(());
+    }
+
+    revert_patches(log, root, dep, &checkout_dir)?;
+    let old_dep = Dependency { vcs: source.vcs.clone(), url: source.url.clone(), ..dep.clone() };
+    let vcs = load_vcs_for(root, &old_dep, offline)?;
+    match vcs.is_dirty(log, root, &old_dep)? {
+        Some(false) => {},
+        Some(true) => return Err(DerpyError::MovedDependencyModified {
+            name: dep.name.clone(),
+            from_url: source.url,
+            to_url: dep.url.clone(),
+        }),
+        None if source.vcs == dep.vcs => return Ok(()),
+        None => return Err(DerpyError::UnableToCheckForModifications {
+            name: dep.name.clone(),
+            vcs: source.vcs,
+        }),
+    }
+
+    remove_dir(&checkout_dir)?;
+    let full_path = dep.get_full_path_in(root);
+    if full_path != checkout_dir && full_path.is_dir() {
+        remove_dir(&full_path)?;
+    }
+    log.event(Event::CheckoutReplaced {
+        name: dep.name.clone(),
+        from_url: source.url,
+        to_url: dep.url.clone(),
+    });
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum AcquireMode {
     Acquire,
     LockTo {
@@ -33,69 +187,220 @@ pub enum AcquireMode {
     Upgrade,
 }
 
-pub fn acquire(log: &Log, dep: &Dependency, acquire_mode: AcquireMode) -> Result<AcquireOutcome, DerpyError> {
-    let vcs = match load_vcs_info(&dep.vcs)? {
-        Some(vcs) => vcs,
-        None => return Err(DerpyError::UnknownVcs { name: dep.vcs.clone() }.into()),
+pub fn acquire(log: &Log, root: &Path, dep: &Dependency, acquire_mode: AcquireMode, offline: bool) -> Result<AcquireOutcome, DerpyError> {
+    let outcome = change_checkout(log, root, dep, acquire_mode, offline)?;
+    run_hooks(log, root, dep, &outcome)?;
+    Ok(outcome)
+}
+
+/// Acquires a dependency that isn't there yet, then brings it to the version asked for, which a
+/// fresh checkout isn't necessarily at. Only the `post_acquire` hook is run, once it is done.
+pub fn acquire_at_version(log: &Log, root: &Path, dep: &Dependency, offline: bool) -> Result<AcquireOutcome, DerpyError> {
+    change_checkout(log, root, dep, AcquireMode::Acquire, offline)?;
+    let outcome = match change_checkout(log, root, dep, AcquireMode::Upgrade, offline)? {
+        AcquireOutcome::UpgradedTo { to_version: at_version, .. } |
+        AcquireOutcome::NoChange { current_version: at_version } => AcquireOutcome::Acquired { at_version },
+        outcome => outcome,
     };
+    run_hooks(log, root, dep, &outcome)?;
+    Ok(outcome)
+}
+
+/// does everything `acquire` does except for running hooks
+fn change_checkout(log: &Log, root: &Path, dep: &Dependency, acquire_mode: AcquireMode, offline: bool) -> Result<AcquireOutcome, DerpyError> {
+    let vcs = load_vcs_for(root, dep, offline)?;
 
-    ensure_dir(&dep.target)?;
+    ensure_dir(dep.get_target_dir_in(root))?;
+    ensure_dir(dep.get_checkout_parent_in(root))?;
 
-    let current_version = if dep.get_full_path().is_dir() {
-        Some(vcs.get_version_of(&log, dep)?)
+    let checkout_dir = vcs.get_checkout_dir(root, dep);
+    let current_version = if checkout_dir.is_dir() {
+        Some(vcs.get_version_of(log, root, dep)?)
     } else {
         None
     };
 
-    if let Some(version) = current_version {
+    // a vendored copy already has its patches applied, otherwise they are taken off before the
+    // VCS changes the checkout and put back on afterwards
+    let is_patched = !is_vendored(root, dep);
+    if is_patched && !dep.patches.is_empty() && dep.vcs == "path" {
+        return Err(DerpyError::PatchedPathDependency { name: dep.name.clone() });
+    }
+    let will_change = match (&current_version, &acquire_mode) {
+        (Some(version), AcquireMode::LockTo { version: locked_version }) => version != locked_version,
+        (Some(_), AcquireMode::Upgrade) => true,
+        (Some(_), AcquireMode::Acquire) => false,
+        (None, _) => true,
+    };
+    if is_patched && will_change {
+        revert_patches(log, root, dep, &checkout_dir)?;
+    }
+
+    let outcome = if let Some(version) = current_version {
         match acquire_mode {
             AcquireMode::Acquire => {
-                Ok(AcquireOutcome::Ignored {
+                AcquireOutcome::Ignored {
                     at_version: version,
-                })
+                }
             },
             AcquireMode::LockTo { version: locked_version } => {
                 if version != locked_version {
-                    vcs.checkout(&log, dep, &locked_version)?;
-                    Ok(AcquireOutcome::Restored {
+                    vcs.checkout(log, root, dep, &locked_version)?;
+                    AcquireOutcome::Restored {
                         to_version: locked_version,
                         from_version: version,
-                    })
+                    }
                 } else {
-                    Ok(AcquireOutcome::NoChange {
+                    AcquireOutcome::NoChange {
                         current_version: version,
-                    })
+                    }
                 }
             },
             AcquireMode::Upgrade => {
-                vcs.upgrade(&log, dep)?;
+                vcs.upgrade(log, root, dep)?;
 
-                let new_version = vcs.get_version_of(&log, dep)?;
+                let new_version = vcs.get_version_of(log, root, dep)?;
                 if new_version != version {
-                    Ok(AcquireOutcome::UpgradedTo {
+                    AcquireOutcome::UpgradedTo {
                         from_version: version,
                         to_version: new_version,
-                    })
+                    }
                 } else {
-                    Ok(AcquireOutcome::NoChange {
+                    AcquireOutcome::NoChange {
                         current_version: version,
-                    })
+                    }
                 }
             },
         }
     } else {
-        if let AcquireMode::Acquire = acquire_mode {
-            vcs.acquire(&log, dep)?;
-
-            Ok(AcquireOutcome::Acquired {
-                at_version: vcs.get_version_of(&log, dep)?,
-            })
-        } else {
-            Err(DerpyError::NonsenseAcquireMode {
-                dependency: dep.name.clone(),
-                acquire_mode,
-            }.into())
+        match acquire_mode {
+            AcquireMode::Acquire => {
+                vcs.acquire(log, root, dep)?;
+                save_source(root, dep)?;
+
+                AcquireOutcome::Acquired {
+                    at_version: vcs.get_version_of(log, root, dep)?,
+                }
+            },
+            AcquireMode::LockTo { version: locked_version } => {
+                vcs.acquire(log, root, dep)?;
+                save_source(root, dep)?;
+                vcs.checkout(log, root, dep, &locked_version)?;
+
+                AcquireOutcome::Acquired {
+                    at_version: vcs.get_version_of(log, root, dep)?,
+                }
+            },
+            AcquireMode::Upgrade => {
+                return Err(DerpyError::NonsenseAcquireMode {
+                    dependency: dep.name.clone(),
+                    acquire_mode,
+                });
+            },
+        }
+    };
+
+    let is_repatched = is_patched && update_patches(log, root, dep, &checkout_dir)?;
+
+    if let Some(ref subdir) = dep.subdir {
+        let is_changed = is_repatched || !matches!(outcome, AcquireOutcome::NoChange { .. } | AcquireOutcome::Ignored { .. });
+        let full_path = dep.get_full_path_in(root);
+        if checkout_dir != full_path && (is_changed || !full_path.is_dir()) {
+            place_subdir(log, dep, &checkout_dir.join(subdir), &full_path)?;
         }
     }
+
+    Ok(outcome)
+}
+
+/// Works out what `acquire` would do with the same arguments, without changing anything. Only the
+/// commands that look at the dependency as it is are run.
+pub fn plan_acquire(log: &Log, root: &Path, dep: &Dependency, acquire_mode: AcquireMode, offline: bool) -> Result<AcquirePlan, DerpyError> {
+    let vcs = load_vcs_for(root, dep, offline)?;
+    let checkout_dir = vcs.get_checkout_dir(root, dep);
+    let current_version = if checkout_dir.is_dir() {
+        Some(vcs.get_version_of(log, root, dep)?)
+    } else {
+        None
+    };
+
+    let mut actions = Vec::new();
+    let (hook, hook_commands, revision) = match (current_version, acquire_mode) {
+        (Some(version), AcquireMode::Acquire) => return Ok(AcquirePlan { actions, revision: Some(version) }),
+        (Some(version), AcquireMode::LockTo { version: locked_version }) => {
+            if version == locked_version {
+                return Ok(AcquirePlan { actions, revision: Some(version) });
+            }
+            actions.push(PlannedAction::Checkout {
+                commands: vcs.checkout_commands(dep, &locked_version)?,
+                to_version: locked_version.clone(),
+            });
+            ("post_checkout", &dep.hooks.post_checkout, Some(locked_version))
+        },
+        (Some(_), AcquireMode::Upgrade) => {
+            actions.push(PlannedAction::Upgrade { commands: vcs.upgrade_commands(dep)? });
+            ("post_upgrade", &dep.hooks.post_upgrade, None)
+        },
+        (None, AcquireMode::Acquire) => {
+            actions.push(PlannedAction::Acquire { commands: vcs.acquire_commands(dep)? });
+            ("post_acquire", &dep.hooks.post_acquire, None)
+        },
+        (None, AcquireMode::LockTo { version: locked_version }) => {
+            actions.push(PlannedAction::Acquire { commands: vcs.acquire_commands(dep)? });
+            actions.push(PlannedAction::Checkout {
+                commands: vcs.checkout_commands(dep, &locked_version)?,
+                to_version: locked_version.clone(),
+            });
+            ("post_acquire", &dep.hooks.post_acquire, Some(locked_version))
+        },
+        (None, acquire_mode @ AcquireMode::Upgrade) => return Err(DerpyError::NonsenseAcquireMode {
+            dependency: dep.name.clone(),
+            acquire_mode,
+        }),
+    };
+
+    if !dep.patches.is_empty() && !is_vendored(root, dep) {
+        actions.push(PlannedAction::ApplyPatches { patches: dep.patches.clone() });
+    }
+    actions.extend(plan_hook(dep, hook, hook_commands, revision.as_deref())?);
+
+    Ok(AcquirePlan { actions, revision })
+}
+
+/// works out what `acquire_at_version` would do, without changing anything
+pub fn plan_at_version(root: &Path, dep: &Dependency, offline: bool) -> Result<AcquirePlan, DerpyError> {
+    let vcs = load_vcs_for(root, dep, offline)?;
+    let mut actions = vec![
+        PlannedAction::Acquire { commands: vcs.acquire_commands(dep)? },
+        PlannedAction::Upgrade { commands: vcs.upgrade_commands(dep)? },
+    ];
+    if !dep.patches.is_empty() && !is_vendored(root, dep) {
+        actions.push(PlannedAction::ApplyPatches { patches: dep.patches.clone() });
+    }
+    actions.extend(plan_hook(dep, "post_acquire", &dep.hooks.post_acquire, None)?);
+
+    Ok(AcquirePlan { actions, revision: None })
 }
 
+/// replaces the dependency's directory with a copy of the subdirectory of its checkout it wants
+fn place_subdir(log: &Log, dep: &Dependency, source: &Path, dest: &Path) -> Result<(), DerpyError> {
+    dep.validate_subdir()?;
+    if !source.is_dir() {
+        return Err(DerpyError::MissingSubdir {
+            name: dep.name.clone(),
+            path: source.into(),
+        });
+    }
+
+    if dest.exists() {
+        remove_dir(dest)?;
+    }
+    log.info(format!("copying {:?} to {:?}", source, dest));
+    if let Err(e) = copy_dir(source, dest, &[]) {
+        return Err(DerpyError::UnableToCopyDirectory {
+            path: source.into(),
+            error: e,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,330 @@
+use std::fs::{create_dir_all, hard_link, read_dir, remove_dir_all, remove_file, File};
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use flate2::read::GzDecoder;
+use xz2::read::XzDecoder;
+use sha2::{Digest, Sha256};
+use tar;
+use zip;
+use dependency::Dependency;
+use error::DerpyError;
+use vcs::{export_dir, Vcs};
+use log::Log;
+
+/// records the hash of the archive a dependency was extracted from, as its version
+const ARCHIVE_HASH_FILE: &str = ".derpy_archive";
+
+enum ArchiveFormat {
+    TarGz,
+    TarXz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    fn of(path: &Path) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Some(ArchiveFormat::TarXz)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Unpacks `.tar.gz`, `.tar.xz` and `.zip` archives from the local filesystem, without relying
+/// on any other tools being installed. The archive's sha256 is checked against the dependency's
+/// `sha256` option, if it has one, before anything is extracted, and is used as its version.
+/// The `strip_components` option removes that many leading directories from every path.
+pub struct ArchiveVcs;
+
+impl ArchiveVcs {
+    /// the archive's location, which is either a path relative to the project or a `file://` url
+    fn archive_path(root: &Path, dependency: &Dependency) -> PathBuf {
+        let url = &dependency.url;
+        root.join(url.strip_prefix("file://").unwrap_or(url))
+    }
+
+    fn strip_components(dependency: &Dependency) -> Result<usize, DerpyError> {
+        match dependency.options.get("strip_components") {
+            Some(value) => match value.parse() {
+                Ok(count) => Ok(count),
+                Err(_) => Err(DerpyError::InvalidDependencyOption {
+                    name: dependency.name.clone(),
+                    option: "strip_components".into(),
+                    value: value.clone(),
+                }),
+            },
+            None => Ok(0),
+        }
+    }
+
+    /// reads the whole archive so that what is extracted is exactly what was checked
+    fn read_archive(path: &Path) -> Result<(Vec<u8>, String), DerpyError> {
+        let mut contents = Vec::new();
+        let result = File::open(path).and_then(|mut file| file.read_to_end(&mut contents));
+        if let Err(e) = result {
+            return Err(DerpyError::UnableToReadArchive {
+                path: path.into(),
+                error: e,
+            });
+        }
+        let hash = format!("{:x}", Sha256::digest(&contents));
+        Ok((contents, hash))
+    }
+
+    /// Extracts the archive into the dependency's directory if its hash is as expected, replacing
+    /// whatever was there before. Nothing is changed if the archive can't be read or is wrong.
+    fn extract(log: &Log, root: &Path, dependency: &Dependency, expected_hash: Option<&str>) -> Result<(), DerpyError> {
+        let path = Self::archive_path(root, dependency);
+        let format = match ArchiveFormat::of(&path) {
+            Some(format) => format,
+            None => return Err(DerpyError::UnknownArchiveFormat { path }),
+        };
+        let strip_components = Self::strip_components(dependency)?;
+
+        let (contents, hash) = Self::read_archive(&path)?;
+        let expected_hash = expected_hash.or_else(|| dependency.options.get("sha256").map(|hash| hash.as_str()));
+        if let Some(expected_hash) = expected_hash {
+            if !expected_hash.eq_ignore_ascii_case(&hash) {
+                return Err(DerpyError::ArchiveChecksumMismatch {
+                    name: dependency.name.clone(),
+                    expected: expected_hash.into(),
+                    actual: hash,
+                });
+            }
+        }
+
+        Self::clear(root, dependency)?;
+        let dest = dependency.get_checkout_dir_in(root);
+        log.info(format!("extracting {:?} into {:?}", path, dest));
+        let result = match format {
+            ArchiveFormat::TarGz => extract_tar(GzDecoder::new(Cursor::new(contents)), &dest, strip_components),
+            ArchiveFormat::TarXz => extract_tar(XzDecoder::new(Cursor::new(contents)), &dest, strip_components),
+            ArchiveFormat::Zip => extract_zip(contents, &dest, strip_components),
+        };
+        let result = result.and_then(|_| {
+            File::create(dest.join(ARCHIVE_HASH_FILE)).and_then(|mut file| file.write_all(hash.as_bytes()))
+        });
+        if let Err(e) = result {
+            return Err(DerpyError::UnableToExtractArchive {
+                path,
+                error: e,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// empties the dependency's directory ready to extract an archive into it
+    fn clear(root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        let dir = dependency.get_checkout_dir_in(root);
+        let result = read_dir(&dir).and_then(|entries| {
+            for entry in entries {
+                let path = entry?.path();
+                if path.is_dir() {
+                    remove_dir_all(&path)?;
+                } else {
+                    remove_file(&path)?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            return Err(DerpyError::FailedToRemoveDirectory {
+                error: e,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Vcs for ArchiveVcs {
+    fn get_name(&self) -> &str { "archive" }
+
+    fn get_version(&self, _log: &Log) -> Result<String, DerpyError> {
+        Ok(format!("derpy {}", env!("CARGO_PKG_VERSION")))
+    }
+
+    fn get_default_version(&self) -> &str { "latest" }
+
+    fn acquire(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        let dest = dependency.get_checkout_dir_in(root);
+        if let Err(e) = create_dir_all(&dest) {
+            return Err(DerpyError::FailedToCreateDirectory {
+                error: e,
+            });
+        }
+        let result = Self::extract(log, root, dependency, None);
+        if result.is_err() {
+            // leave nothing behind, so that the next attempt starts afresh
+            let _ = remove_dir_all(&dest);
+        }
+        result
+    }
+
+    fn checkout(&self, log: &Log, root: &Path, dependency: &Dependency, at_version: &str) -> Result<(), DerpyError> {
+        Self::extract(log, root, dependency, Some(at_version))
+    }
+
+    fn upgrade(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        Self::extract(log, root, dependency, None)
+    }
+
+    fn get_version_of(&self, _log: &Log, root: &Path, dependency: &Dependency) -> Result<String, DerpyError> {
+        let path = dependency.get_checkout_dir_in(root).join(ARCHIVE_HASH_FILE);
+        let mut hash = String::new();
+        if let Err(e) = File::open(&path).and_then(|mut file| file.read_to_string(&mut hash)) {
+            return Err(DerpyError::UnableToReadArchive {
+                path,
+                error: e,
+            });
+        }
+        Ok(hash.trim().into())
+    }
+
+    fn is_dirty(&self, _log: &Log, _root: &Path, _dependency: &Dependency) -> Result<Option<bool>, DerpyError> {
+        Ok(None)
+    }
+
+    fn export(&self, log: &Log, root: &Path, dependency: &Dependency, dest: &Path) -> Result<(), DerpyError> {
+        export_dir(log, &dependency.get_full_path_in(root), dest, &[ARCHIVE_HASH_FILE])
+    }
+}
+
+/// the path an archive entry should be extracted to, or `None` if it is stripped away entirely or
+/// would end up outside of the destination
+fn strip_path(path: &Path, strip_components: usize) -> Option<PathBuf> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => components.push(part),
+            Component::CurDir => {},
+            _ => return None,
+        }
+    }
+    if components.len() <= strip_components {
+        return None;
+    }
+    Some(components[strip_components..].iter().collect())
+}
+
+/// the error for an archive entry that would write to, or link to, somewhere outside of `dest`
+fn outside_dest(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("archive entry {:?} leads outside of the dependency's directory", path))
+}
+
+/// Fails unless `target`, a path within `dest` (which must be canonical), stays within it once any
+/// symlinks already extracted along the way are followed. Parts that don't exist yet are created
+/// by derpy as plain directories, so only those that do exist need to be looked at.
+fn check_within(dest: &Path, target: &Path) -> io::Result<()> {
+    let relative = match target.strip_prefix(dest) {
+        Ok(relative) => relative,
+        Err(_) => return Err(outside_dest(target)),
+    };
+    let mut current = dest.to_path_buf();
+    for component in relative.components() {
+        current.push(component);
+        if current.symlink_metadata().is_err() {
+            break;
+        }
+        match current.canonicalize() {
+            Ok(ref resolved) if resolved.starts_with(dest) => {},
+            _ => return Err(outside_dest(target)),
+        }
+    }
+    Ok(())
+}
+
+/// Fails if a symlink at `target` pointing at `link_name` would point outside of `dest`, going by
+/// the names alone. Absolute links are never allowed.
+fn check_link_within(dest: &Path, target: &Path, link_name: &Path) -> io::Result<()> {
+    let mut resolved = match target.parent() {
+        Some(parent) => parent.to_path_buf(),
+        None => return Err(outside_dest(target)),
+    };
+    for component in link_name.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {},
+            Component::ParentDir if resolved != dest => { resolved.pop(); },
+            _ => return Err(outside_dest(target)),
+        }
+    }
+    if !resolved.starts_with(dest) {
+        return Err(outside_dest(target));
+    }
+    Ok(())
+}
+
+fn extract_tar<R: Read>(reader: R, dest: &Path, strip_components: usize) -> io::Result<()> {
+    let dest = dest.canonicalize()?;
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let target = match strip_path(&entry.path()?, strip_components) {
+            Some(path) => dest.join(path),
+            None => continue,
+        };
+        check_within(&dest, &target)?;
+
+        let entry_type = entry.header().entry_type();
+        let link_name = entry.link_name()?.map(|link_name| link_name.into_owned());
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent)?;
+        }
+        // an existing symlink is replaced rather than followed
+        if target.symlink_metadata().map(|metadata| metadata.file_type().is_symlink()).unwrap_or(false) {
+            remove_file(&target)?;
+        }
+
+        if entry_type.is_symlink() {
+            match link_name {
+                Some(ref link_name) => check_link_within(&dest, &target, link_name)?,
+                None => return Err(outside_dest(&target)),
+            }
+        } else if entry_type.is_hard_link() {
+            // hard links name another entry in the archive, so are stripped in the same way
+            let source = match link_name.and_then(|link_name| strip_path(&link_name, strip_components)) {
+                Some(path) => dest.join(path),
+                None => return Err(outside_dest(&target)),
+            };
+            check_within(&dest, &source)?;
+            hard_link(&source, &target)?;
+            continue;
+        }
+        entry.unpack(&target)?;
+    }
+    Ok(())
+}
+
+fn extract_zip(contents: Vec<u8>, dest: &Path, strip_components: usize) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(contents))?;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let target = match file.enclosed_name().and_then(|path| strip_path(path, strip_components)) {
+            Some(path) => dest.join(path),
+            None => continue,
+        };
+        if file.is_dir() {
+            create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent)?;
+        }
+        io::copy(&mut file, &mut File::create(&target)?)?;
+        #[cfg(unix)]
+        {
+            use std::fs::{set_permissions, Permissions};
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(mode) = file.unix_mode() {
+                set_permissions(&target, Permissions::from_mode(mode))?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -10,6 +10,13 @@ pub fn parse_option_key_value(text: &str) -> Result<(String, String), String> {
     }
 }
 
+pub fn validate_job_count(text: String) -> Result<(), String> {
+    match text.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(()),
+        _ => Err("job count must be a whole number greater than zero".into()),
+    }
+}
+
 pub fn validate_option_key_value(text: String) -> Result<(), String> {
     match parse_option_key_value(&text) {
         Ok(_) => Ok(()),
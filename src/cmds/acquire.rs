@@ -1,62 +1,263 @@
-use derpyfile::{DerpyFile, load_config, save_config};
-use acquire::{acquire, AcquireMode, AcquireOutcome};
-use consts::{CONFIG_FILE, CONFIG_LOCK_FILE};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::fs::symlink_metadata;
+use lockfile::{LockFile, LockedDependency, load_lock, save_lock};
+use acquire::{acquire, acquire_at_version, plan_acquire, plan_at_version, replace_moved_checkout, AcquireMode, AcquireOutcome, CheckoutSource};
+use versions::{is_version_range, resolve_version_range};
+use vcs::load_vcs_for;
+use hooks::run_hooks;
+use patches::wanted_patches;
+use consts::{CONFIG_FILE, CONFIG_LOCK_FILE, CONFIG_OVERRIDE_FILE};
+use resolve::walk_dependencies;
+use derpyfile::{DerpyFile, load_config};
+use overrides::load_overrides;
+use vendor::VendorDir;
+use path_utils::remove_symlink;
 use cmds::CommandContext;
-use error::DerpyError;
+use events::{Event, LockChange};
+use log::Log;
+use dependency::Dependency;
+use error::{DerpyError, MissingDependencies};
 
 pub fn cli_acquire(context: CommandContext) -> Result<(), DerpyError> {
     let config_path = context.path.join(CONFIG_FILE);
     let config = load_config(&config_path)?;
 
     let lock_path = context.path.join(CONFIG_LOCK_FILE);
-    let mut lock = if lock_path.is_file() {
-        load_config(&lock_path)?
+    let lock = if lock_path.is_file() {
+        load_lock(&lock_path)?
     } else {
-        DerpyFile::default()
+        LockFile::default()
     };
-    let mut lock_file_updated = false;
 
-    for (name, dep) in config.dependencies.iter() {
-        let acquire_mode = match lock.dependencies.get(name).map(|d| d.version.clone()) {
-            Some(version) => AcquireMode::LockTo { version },
-            _ => AcquireMode::Acquire,
+    if let Some(new_lock) = acquire_dependencies(&context, &config, &lock, |_| false)? {
+        if new_lock != lock {
+            save_lock(&new_lock, &lock_path)?;
+            context.log.event(Event::LockFileUpdated);
+        }
+    }
+
+    Ok(())
+}
+
+/// Acquires every dependency reachable from `config`, upgrading those that `should_upgrade`
+/// selects and respecting the lock file for the rest. Lock entries that no longer match the
+/// config entry they were locked from are ignored. Returns the lock file describing the result,
+/// or `None` for a dry run, which only reports what it would do (including the commands it would
+/// run) and leaves everything as it is.
+///
+/// Overridden dependencies are always brought up to date with their override, and their lock
+/// entries are carried over untouched, so that the lock file still describes the shared config.
+///
+/// Given a vendor directory, dependencies are copied from there rather than acquired with their
+/// version control systems. Dependencies whose version is a range are resolved to a tag when they
+/// are first acquired or upgraded, staying within the range unless `breaking` is given (by the
+/// upgrade command), and the lock file keeps them at that tag afterwards.
+///
+/// When offline, every dependency that can't be acquired without the network is collected up and
+/// reported together, rather than stopping at the first.
+pub fn acquire_dependencies<F>(context: &CommandContext, config: &DerpyFile, lock: &LockFile, should_upgrade: F) -> Result<Option<LockFile>, DerpyError>
+    where F: Fn(&str) -> bool + Sync
+{
+    let jobs = match context.matches.value_of("jobs") {
+        Some(jobs) => jobs.parse().unwrap(),
+        None => 1,
+    };
+    let revisions = Mutex::new(BTreeMap::new());
+    let missing_offline = Mutex::new(Vec::new());
+    let overrides = load_overrides(context.path.join(CONFIG_OVERRIDE_FILE))?;
+    let vendored = match context.matches.value_of("vendored") {
+        Some(dir) => Some(VendorDir::load(context.path.join(dir))?),
+        None => None,
+    };
+
+    let breaking = context.matches.is_present("breaking");
+    let dry_run = context.matches.is_present("dry_run");
+
+    let root = &context.path;
+    let offline = context.offline;
+    let acquire_one = |log: &Log, dep: &Dependency| -> Result<(), DerpyError> {
+        let name = &dep.name;
+        let full_path = dep.get_full_path_in(root);
+        let resolve_tag = || -> Result<String, DerpyError> {
+            let vcs = load_vcs_for(root, dep, offline)?;
+            resolve_version_range(log, &*vcs, root, dep, breaking)
         };
-        let new_lock_version = match acquire(&context.log, dep, acquire_mode)? {
-            AcquireOutcome::Acquired { at_version } => {
-                println!("- acquired '{}' at version {}", name, at_version);
-                Some(at_version)
-            },
-            AcquireOutcome::Restored { from_version, to_version } => {
-                println!("- restored '{}' to {} from {}", name, to_version, from_version);
+        let tagged;
+        if overrides.is_overridden(name) {
+            log.event(Event::Overridden { name: name.clone() });
+            replace_moved_checkout(log, root, dep, None, offline, dry_run)?;
+            let tag = if is_version_range(&dep.version) {
+                Some(resolve_tag()?)
+            } else {
                 None
-            },
-            AcquireOutcome::UpgradedTo { from_version, to_version } => {
-                println!("- upgraded '{}' to {} from {}", name, to_version, from_version);
-                Some(to_version)
-            },
-            AcquireOutcome::NoChange { current_version } => {
-                println!("- '{}' up to date at version {}", name, current_version);
+            };
+            let dep = match tag {
+                Some(ref tag) => {
+                    tagged = Dependency { version: tag.clone(), ..dep.clone() };
+                    &tagged
+                },
+                None => dep,
+            };
+            // a fresh checkout of a tag goes straight to it, like one held by the lock file
+            let fresh_mode = tag.map(|version| AcquireMode::LockTo { version });
+            if dry_run {
+                let plan = match fresh_mode {
+                    _ if full_path.is_dir() => plan_acquire(log, root, dep, AcquireMode::Upgrade, offline)?,
+                    Some(fresh_mode) => plan_acquire(log, root, dep, fresh_mode, offline)?,
+                    None => plan_at_version(root, dep, offline)?,
+                };
+                log.event(Event::Planned {
+                    name: name.clone(),
+                    actions: plan.actions,
+                    lock_change: None,
+                });
+                return Ok(());
+            }
+            let outcome = match fresh_mode {
+                _ if full_path.is_dir() => acquire(log, root, dep, AcquireMode::Upgrade, offline)?,
+                Some(fresh_mode) => acquire(log, root, dep, fresh_mode, offline)?,
+                None => acquire_at_version(log, root, dep, offline)?,
+            };
+            log.event(Event::Acquire {
+                name: name.clone(),
+                outcome,
+                commands: log.take_commands(),
+            });
+            return Ok(());
+        }
+
+        let is_symlink = symlink_metadata(&full_path).map(|metadata| metadata.file_type().is_symlink()).unwrap_or(false);
+        if is_symlink && dep.vcs != "path" && dry_run {
+            log.info(format!("would remove symlink {:?} left behind by an override", full_path));
+        } else if is_symlink && dep.vcs != "path" {
+            log.info(format!("removing symlink {:?} left behind by an override", full_path));
+            remove_symlink(&full_path)?;
+        }
+        // a checkout from before derpy recorded where checkouts came from is taken to be as locked
+        let locked_source = lock.dependencies.get(name).map(|locked| CheckoutSource {
+            vcs: locked.vcs.clone(),
+            url: locked.url.clone(),
+        });
+        replace_moved_checkout(log, root, dep, locked_source, offline, dry_run)?;
+
+        let locked = match lock.dependencies.get(name) {
+            Some(locked) if !locked.is_locked_from(dep) => {
+                log.event(Event::LockEntryChanged {
+                    name: name.clone(),
+                    locked_version: locked.revision.clone(),
+                });
                 None
             },
-            AcquireOutcome::Ignored { at_version } => {
-                println!("- warning: ignored '{}' - left at version {}", name, at_version);
-                println!("  (dependency {} present but has no lock file entry)", name);
-                None
+            locked => locked,
+        };
+        let is_stale = locked.is_none() && lock.dependencies.contains_key(name);
+        // only a checkout that is there can be upgraded, a missing one is acquired at its newest
+        let acquire_mode = match locked {
+            _ if (should_upgrade(name) || is_stale) && full_path.is_dir() => AcquireMode::Upgrade,
+            Some(locked) if !should_upgrade(name) => AcquireMode::LockTo { version: locked.revision.clone() },
+            _ => AcquireMode::Acquire,
+        };
+        let is_range = is_version_range(&dep.version) && vendored.is_none();
+        let tag = match acquire_mode {
+            AcquireMode::LockTo { .. } => locked.and_then(|locked| locked.tag.clone()),
+            AcquireMode::Upgrade if is_range => Some(resolve_tag()?),
+            // an existing checkout that was never locked is left as it is, as with any other version
+            AcquireMode::Acquire if is_range && !full_path.exists() => Some(resolve_tag()?),
+            _ => None,
+        };
+        let dep = match tag {
+            Some(ref tag) if is_version_range(&dep.version) => {
+                tagged = Dependency { version: tag.clone(), ..dep.clone() };
+                &tagged
+            },
+            _ => dep,
+        };
+        let acquire_mode = match (acquire_mode, &tag) {
+            (AcquireMode::Acquire, Some(tag)) => AcquireMode::LockTo { version: tag.clone() },
+            (acquire_mode, _) => acquire_mode,
+        };
+
+        if dry_run {
+            let plan = plan_acquire(log, root, dep, acquire_mode, offline)?;
+            let lock_change = match lock.dependencies.get(name) {
+                _ if plan.actions.is_empty() => None,
+                Some(old) if Some(&old.revision) == plan.revision.as_ref() => None,
+                Some(old) => Some(LockChange::Updated {
+                    from_revision: old.revision.clone(),
+                    to_revision: plan.revision,
+                }),
+                None => Some(LockChange::Added { revision: plan.revision }),
+            };
+            log.event(Event::Planned {
+                name: name.clone(),
+                actions: plan.actions,
+                lock_change,
+            });
+            return Ok(());
+        }
+
+        let outcome = match vendored {
+            Some(ref vendored) => {
+                let outcome = vendored.restore(log, root, dep, locked.map(|locked| locked.revision.as_str()))?;
+                run_hooks(log, root, dep, &outcome)?;
+                outcome
             },
+            None => acquire(log, root, dep, acquire_mode, offline)?,
+        };
+        let revision = match outcome {
+            AcquireOutcome::Acquired { ref at_version } => Some(at_version.clone()),
+            AcquireOutcome::Restored { ref to_version, .. } => Some(to_version.clone()),
+            AcquireOutcome::UpgradedTo { ref to_version, .. } => Some(to_version.clone()),
+            AcquireOutcome::NoChange { ref current_version } => Some(current_version.clone()),
+            AcquireOutcome::Ignored { .. } => None,
         };
+        log.event(Event::Acquire {
+            name: name.clone(),
+            outcome,
+            commands: log.take_commands(),
+        });
 
-        if let Some(version) = new_lock_version {
-            let mut dependency = dep.clone();
-            lock_file_updated = true;
-            dependency.version = version;
-            lock.dependencies.insert(dep.name.clone(), dependency);
+        if let Some(revision) = revision {
+            revisions.lock().unwrap().insert(name.clone(), (revision, tag));
         }
+
+        Ok(())
+    };
+    let resolved = walk_dependencies(&context.log, root, config, &overrides, jobs, |log, dep| {
+        match acquire_one(log, dep) {
+            Err(DerpyError::UnavailableOffline { name, reason }) => {
+                missing_offline.lock().unwrap().push((name, reason));
+                Ok(())
+            },
+            result => result,
+        }
+    })?;
+
+    let missing_offline = missing_offline.into_inner().unwrap();
+    if !missing_offline.is_empty() {
+        return Err(DerpyError::MissingOffline(MissingDependencies(missing_offline)));
     }
 
-    if lock_file_updated {
-        save_config(&lock, &lock_path)?;
-        println!("lock file updated");
+    for name in lock.dependencies.keys().filter(|name| !resolved.contains_key(*name)) {
+        context.log.event(Event::NoLongerRequired { name: name.clone() });
+    }
+    if dry_run {
+        return Ok(None);
     }
 
-    Ok(())
+    let mut revisions = revisions.into_inner().unwrap();
+    let mut new_lock = LockFile::default();
+    for (name, resolved_dep) in resolved.iter() {
+        if overrides.is_overridden(name) {
+            if let Some(locked) = lock.dependencies.get(name) {
+                new_lock.dependencies.insert(name.clone(), locked.clone());
+            }
+        } else if let Some((revision, tag)) = revisions.remove(name) {
+            let patches = wanted_patches(root, &resolved_dep.dependency)?;
+            new_lock.dependencies.insert(name.clone(), LockedDependency::new(resolved_dep, revision, tag, patches));
+        }
+    }
+
+    Ok(Some(new_lock))
 }
@@ -4,7 +4,8 @@ use arg_utils::parse_option_key_value;
 use std::collections::BTreeMap;
 use dependency::Dependency;
 use cmds::CommandContext;
-use vcs::load_vcs_info;
+use events::Event;
+use vcs::load_vcs;
 use error::DerpyError;
 
 pub fn cli_add(context: CommandContext) -> Result<(), DerpyError> {
@@ -13,10 +14,11 @@ pub fn cli_add(context: CommandContext) -> Result<(), DerpyError> {
     let url = context.matches.value_of("url").unwrap().to_string();
     let version = context.matches.value_of("version");
     let target = context.matches.value_of("target").unwrap_or(DEPENDENCY_DIR).to_string();
+    let subdir = context.matches.value_of("subdir").map(|subdir| subdir.to_string());
 
-    let vcs_info = match load_vcs_info(&vcs)? {
+    let vcs_info = match load_vcs(&context.path, &vcs, context.offline)? {
         Some(info) => info,
-        None => return Err(DerpyError::UnknownVcs { name: vcs.into() }.into()),
+        None => return Err(DerpyError::UnknownVcs { name: vcs }),
     };
 
     let _vcs_version = match vcs_info.get_version(&context.log) {
@@ -25,7 +27,7 @@ pub fn cli_add(context: CommandContext) -> Result<(), DerpyError> {
             Some(version)
         },
         Err(_) => {
-            println!("warning: unable to determine version of {}, is it installed?", vcs_info.get_name());
+            context.log.event(Event::VcsNotFound { vcs: vcs_info.get_name().into() });
             None
         },
     };
@@ -47,13 +49,36 @@ pub fn cli_add(context: CommandContext) -> Result<(), DerpyError> {
         version,
         target,
         options,
+        subdir,
+        hooks: Default::default(),
+        patches: Vec::new(),
+        allow_external: context.matches.is_present("allow_external"),
     };
 
     let config_path = context.path.join(CONFIG_FILE);
     let mut config = load_config(&config_path)?;
 
+    dependency.validate_name()?;
+
     if config.dependencies.contains_key(&name) {
-        return Err(DerpyError::DependencyAlreadyExists { name: name.into() }.into());
+        return Err(DerpyError::DependencyAlreadyExists { name });
+    }
+
+    if !dependency.allow_external && !dependency.is_within_project() {
+        return Err(DerpyError::DependencyOutsideProject {
+            name,
+            path: dependency.get_full_path(),
+        });
+    }
+
+    dependency.validate_subdir()?;
+
+    if let Some(other) = config.dependencies.values().find(|other| other.shares_path_with(&dependency)) {
+        return Err(DerpyError::DependencyPathClash {
+            name,
+            other_name: other.name.clone(),
+            path: dependency.get_full_path(),
+        });
     }
 
     config.dependencies.insert(name, dependency);
@@ -6,8 +6,8 @@ use error::DerpyError;
 pub fn cli_init(context: CommandContext) -> Result<(), DerpyError> {
     let config_path = context.path.join(CONFIG_FILE);
     if config_path.is_file() {
-        return Err(DerpyError::AlreadyInitialised.into());
+        return Err(DerpyError::AlreadyInitialised);
     }
-    Ok(save_config(&Default::default(), config_path)?)
+    save_config(&Default::default(), config_path)
 }
 
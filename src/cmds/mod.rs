@@ -2,28 +2,37 @@ use path_utils::determine_cwd;
 use std::path::PathBuf;
 use error::DerpyError;
 use clap::ArgMatches;
-use log::Log;
+use log::{Log, OutputFormat};
 
 mod init;
 mod add;
 mod acquire;
 mod upgrade;
+mod remove;
+mod status;
+mod outdated;
+mod vcs;
+mod vendor;
 
 pub struct CommandContext<'a> {
     pub matches: ArgMatches<'a>,
     pub path: PathBuf,
     pub log: Log,
+    /// whether only VCS commands that don't need the network may be run
+    pub offline: bool,
 }
 
 impl<'a> CommandContext<'a> {
     pub fn from_args(matches: ArgMatches<'a>) -> Result<Self, DerpyError> {
         let path = determine_cwd(matches.value_of("path"))?;
-        let log = Log::from(matches.occurrences_of("verbosity"));
+        let log = Log::new(matches.occurrences_of("verbosity"), OutputFormat::from(matches.value_of("format")));
+        let offline = matches.is_present("offline");
 
         Ok(Self {
             matches,
             path,
             log,
+            offline,
         })
     }
 }
@@ -32,3 +41,8 @@ pub use self::init::cli_init;
 pub use self::add::cli_add;
 pub use self::acquire::cli_acquire;
 pub use self::upgrade::cli_upgrade;
+pub use self::remove::cli_remove;
+pub use self::status::cli_status;
+pub use self::outdated::cli_outdated;
+pub use self::vcs::{cli_vcs_dump, cli_vcs_list};
+pub use self::vendor::cli_vendor;
@@ -0,0 +1,88 @@
+use std::path::Path;
+use lockfile::{LockFile, load_lock};
+use consts::{CONFIG_FILE, CONFIG_LOCK_FILE, CONFIG_OVERRIDE_FILE};
+use resolve::walk_dependencies;
+use derpyfile::load_config;
+use overrides::load_overrides;
+use dependency::Dependency;
+use events::{Event, UpstreamVersion};
+use cmds::CommandContext;
+use vcs::{load_vcs, Vcs};
+use versions::{highest_matching, is_version_range, parse_version_range};
+use error::DerpyError;
+use log::Log;
+
+/// The newest version on the dependency's branch (or within its version range) and the newest of
+/// all, found by asking upstream rather than by looking at what is checked out.
+fn latest_versions(log: &Log, root: &Path, vcs: &dyn Vcs, dep: &Dependency) -> Result<(Option<UpstreamVersion>, Option<UpstreamVersion>), DerpyError> {
+    if !is_version_range(&dep.version) {
+        let latest = vcs.remote_version(log, root, dep)?.map(|revision| UpstreamVersion {
+            tag: None,
+            revision: Some(revision),
+        });
+        return Ok((latest.clone(), latest));
+    }
+
+    let req = parse_version_range(dep)?;
+    let tags = match vcs.list_versions(log, root, dep)? {
+        Some(tags) => tags,
+        None => return Ok((None, None)),
+    };
+    let at_tag = |tag: Option<&str>| -> Result<Option<UpstreamVersion>, DerpyError> {
+        let tag = match tag {
+            Some(tag) => tag.to_string(),
+            None => return Ok(None),
+        };
+        let tagged = Dependency { version: tag.clone(), ..dep.clone() };
+        Ok(Some(UpstreamVersion {
+            revision: vcs.remote_version(log, root, &tagged)?,
+            tag: Some(tag),
+        }))
+    };
+    Ok((at_tag(highest_matching(&tags, Some(&req)))?, at_tag(highest_matching(&tags, None))?))
+}
+
+/// Reports how each dependency's locked version compares to the newest versions upstream, without
+/// changing any dependency or the lock file.
+pub fn cli_outdated(context: CommandContext) -> Result<(), DerpyError> {
+    let config_path = context.path.join(CONFIG_FILE);
+    let config = load_config(&config_path)?;
+
+    let lock_path = context.path.join(CONFIG_LOCK_FILE);
+    let lock = if lock_path.is_file() {
+        load_lock(&lock_path)?
+    } else {
+        LockFile::default()
+    };
+
+    let overrides = load_overrides(context.path.join(CONFIG_OVERRIDE_FILE))?;
+    let jobs = match context.matches.value_of("jobs") {
+        Some(jobs) => jobs.parse().unwrap(),
+        None => 1,
+    };
+
+    let root = &context.path;
+    let offline = context.offline;
+    walk_dependencies(&context.log, root, &config, &overrides, jobs, |log, dep| {
+        // the VCS named, even for a vendored copy, as only upstream is looked at
+        let vcs = match load_vcs(root, &dep.vcs, offline)? {
+            Some(vcs) => vcs,
+            None => return Err(DerpyError::UnknownVcs { name: dep.vcs.clone() }),
+        };
+        let (latest_compatible, latest) = latest_versions(log, root, vcs.as_ref(), dep)?;
+        let locked = lock.dependencies.get(&dep.name);
+
+        log.event(Event::Outdated {
+            name: dep.name.clone(),
+            locked_revision: locked.map(|locked| locked.revision.clone()),
+            locked_tag: locked.and_then(|locked| locked.tag.clone()),
+            latest_compatible,
+            latest,
+            overridden: overrides.is_overridden(&dep.name),
+        });
+
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use lockfile::{load_lock, save_lock};
+use consts::{CONFIG_FILE, CONFIG_LOCK_FILE};
+use derpyfile::{load_config, save_config};
+use path_utils::remove_dir;
+use cmds::CommandContext;
+use events::Event;
+use vcs::load_vcs_for;
+use patches::{forget_patches, is_modified};
+use error::DerpyError;
+
+pub fn cli_remove(context: CommandContext) -> Result<(), DerpyError> {
+    let name = context.matches.value_of("name").unwrap().to_string();
+    let delete = context.matches.is_present("delete");
+    let force = context.matches.is_present("force");
+
+    let config_path = context.path.join(CONFIG_FILE);
+    let mut config = load_config(&config_path)?;
+
+    let dependency = match config.dependencies.remove(&name) {
+        Some(dependency) => dependency,
+        None => return Err(DerpyError::UnknownDependency { name }),
+    };
+
+    let full_path = dependency.get_full_path_in(&context.path);
+    if delete && full_path.is_dir() {
+        if !force {
+            let vcs_info = load_vcs_for(&context.path, &dependency, context.offline)?;
+
+            match is_modified(vcs_info.as_ref(), &context.log, &context.path, &dependency)? {
+                Some(false) => {},
+                Some(true) => return Err(DerpyError::DependencyModified { name }),
+                None => return Err(DerpyError::UnableToCheckForModifications {
+                    name,
+                    vcs: dependency.vcs,
+                }),
+            }
+        }
+
+        remove_dir(&full_path)?;
+        context.log.event(Event::Deleted { path: full_path });
+
+        // a dependency that only wants a subdirectory also has the checkout it was copied from
+        let checkout_dir = dependency.get_checkout_dir_in(&context.path);
+        if dependency.subdir.is_some() && checkout_dir.is_dir() {
+            remove_dir(&checkout_dir)?;
+            context.log.event(Event::Deleted { path: checkout_dir });
+        }
+        forget_patches(&context.path, &dependency)?;
+    }
+
+    save_config(&config, &config_path)?;
+    context.log.event(Event::Removed { name: name.clone() });
+
+    let lock_path = context.path.join(CONFIG_LOCK_FILE);
+    if lock_path.is_file() {
+        let mut lock = load_lock(&lock_path)?;
+        if lock.dependencies.remove(&name).is_some() {
+            save_lock(&lock, &lock_path)?;
+            context.log.event(Event::LockFileUpdated);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::fs::read_dir;
+use std::path::Path;
+use lockfile::{LockFile, load_lock};
+use consts::{CONFIG_FILE, CONFIG_LOCK_FILE, CONFIG_OVERRIDE_FILE, DEPENDENCY_DIR};
+use resolve::walk_dependencies;
+use derpyfile::load_config;
+use overrides::load_overrides;
+use dependency::Dependency;
+use events::{DependencyStatus, Event};
+use cmds::CommandContext;
+use vcs::load_vcs_for;
+use patches::is_modified;
+use error::DerpyError;
+use log::Log;
+
+fn dependency_status(log: &Log, root: &Path, lock: &LockFile, dep: &Dependency) -> Result<(DependencyStatus, Option<bool>), DerpyError> {
+    let vcs = load_vcs_for(root, dep, true)?;
+    let locked_version = lock.dependencies.get(&dep.name).map(|locked| locked.revision.clone());
+
+    if !dep.get_full_path_in(root).is_dir() || !vcs.get_checkout_dir(root, dep).is_dir() {
+        return Ok((DependencyStatus::Missing { locked_version }, None));
+    }
+
+    let current_version = vcs.get_version_of(log, root, dep)?;
+    let is_dirty = is_modified(vcs.as_ref(), log, root, dep)?;
+    let status = match locked_version {
+        None => DependencyStatus::Unlocked { current_version },
+        Some(locked_version) => if locked_version == current_version {
+            DependencyStatus::AtLockedVersion { current_version }
+        } else {
+            DependencyStatus::AtOtherVersion { current_version, locked_version }
+        },
+    };
+
+    Ok((status, is_dirty))
+}
+
+pub fn cli_status(context: CommandContext) -> Result<(), DerpyError> {
+    let config_path = context.path.join(CONFIG_FILE);
+    let config = load_config(&config_path)?;
+
+    let lock_path = context.path.join(CONFIG_LOCK_FILE);
+    let lock = if lock_path.is_file() {
+        load_lock(&lock_path)?
+    } else {
+        LockFile::default()
+    };
+
+    let overrides = load_overrides(context.path.join(CONFIG_OVERRIDE_FILE))?;
+
+    let root = &context.path;
+    let resolved = walk_dependencies(&context.log, root, &config, &overrides, 1, |log, dep| {
+        let name = &dep.name;
+        let overridden = overrides.is_overridden(name);
+        let (status, is_dirty) = dependency_status(log, root, &lock, dep)?;
+        let changed_since_locked = match lock.dependencies.get(name) {
+            Some(locked) => !overridden && !locked.is_locked_from(dep),
+            None => false,
+        };
+
+        log.event(Event::Status {
+            name: name.clone(),
+            status,
+            local_modifications: is_dirty,
+            changed_since_locked,
+            overridden,
+        });
+
+        if is_dirty.is_none() && dep.get_full_path_in(root).is_dir() {
+            log.info(format!("  (unable to tell whether dependency {} has local modifications)", name));
+        }
+
+        Ok(())
+    })?;
+
+    let dependency_paths = resolved.values()
+        .map(|resolved_dep| resolved_dep.dependency.get_full_path_in(root))
+        .collect::<Vec<_>>();
+    if let Ok(entries) = read_dir(root.join(DEPENDENCY_DIR)) {
+        let mut unreferenced = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir() && !dependency_paths.contains(path))
+            .collect::<Vec<_>>();
+        unreferenced.sort();
+        for path in unreferenced {
+            let path = path.strip_prefix(root).map(|path| path.to_path_buf()).unwrap_or(path);
+            context.log.event(Event::Unreferenced { path });
+        }
+    }
+
+    Ok(())
+}
@@ -1,9 +1,9 @@
-use derpyfile::{DerpyFile, load_config, save_config};
-use acquire::{acquire, AcquireMode, AcquireOutcome};
+use lockfile::{LockFile, load_lock, save_lock};
 use consts::{CONFIG_FILE, CONFIG_LOCK_FILE};
-use std::collections::BTreeMap;
-use dependency::Dependency;
+use cmds::acquire::acquire_dependencies;
+use derpyfile::load_config;
 use cmds::CommandContext;
+use events::Event;
 use error::DerpyError;
 
 pub fn cli_upgrade(context: CommandContext) -> Result<(), DerpyError> {
@@ -11,63 +11,28 @@ pub fn cli_upgrade(context: CommandContext) -> Result<(), DerpyError> {
     let config = load_config(&config_path)?;
 
     let lock_path = context.path.join(CONFIG_LOCK_FILE);
-    let mut lock = if lock_path.is_file() {
-        load_config(&lock_path)?
+    let lock = if lock_path.is_file() {
+        load_lock(&lock_path)?
     } else {
-        DerpyFile::default()
+        LockFile::default()
     };
-    let mut lock_file_updated = false;
 
-    let to_upgrade = if context.matches.is_present("all") {
-        config.dependencies.clone()
-    } else {
-        let to_upgrade_names = context.matches.values_of("dependencies").unwrap()
-            .map(|s| s.into())
-            .collect::<Vec<_>>();
-        config.dependencies.iter()
-            .filter(|pair| to_upgrade_names.contains(pair.0))
-            .map(|pair| (pair.0.clone(), pair.1.clone()))
-            .collect::<BTreeMap<String, Dependency>>()
+    let upgrade_all = context.matches.is_present("all");
+    let to_upgrade_names = match context.matches.values_of("dependencies") {
+        Some(values) => values.map(|s| s.to_string()).collect::<Vec<_>>(),
+        None => Vec::new(),
     };
 
-    for (name, dep) in to_upgrade {
-        let new_lock_version = match acquire(&context.log, &dep, AcquireMode::Upgrade)? {
-            AcquireOutcome::Acquired { at_version } => {
-                println!("- acquired '{}' at version {}", name, at_version);
-                Some(at_version)
-            },
-            AcquireOutcome::Restored { from_version, to_version } => {
-                println!("- restored '{}' to {} from {}", name, to_version, from_version);
-                None
-            },
-            AcquireOutcome::UpgradedTo { from_version, to_version } => {
-                println!("- upgraded '{}' to {} from {}", name, to_version, from_version);
-                Some(to_version)
-            },
-            AcquireOutcome::NoChange { current_version } => {
-                println!("- '{}' up to date at version {}", name, current_version);
-                None
-            },
-            AcquireOutcome::Ignored { at_version } => {
-                println!("- warning: ignored '{}' - left at version {}", name, at_version);
-                println!("  (dependency {} present but has no lock file entry)", name);
-                None
-            },
-        };
+    let new_lock = acquire_dependencies(&context, &config, &lock, |name| {
+        upgrade_all || to_upgrade_names.iter().any(|n| n == name)
+    })?;
 
-        if let Some(version) = new_lock_version {
-            let mut dependency = dep.clone();
-            lock_file_updated = true;
-            dependency.version = version;
-            lock.dependencies.insert(dep.name.clone(), dependency);
+    if let Some(new_lock) = new_lock {
+        if new_lock != lock {
+            save_lock(&new_lock, &lock_path)?;
+            context.log.event(Event::LockFileUpdated);
         }
     }
 
-    if lock_file_updated {
-        save_config(&lock, &lock_path)?;
-        println!("lock file updated");
-    }
-
     Ok(())
 }
-
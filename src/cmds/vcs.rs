@@ -0,0 +1,26 @@
+use cmds::CommandContext;
+use events::Event;
+use vcs::{built_in_vcs_info, find_all_vcs_info};
+use error::DerpyError;
+
+pub fn cli_vcs_list(context: CommandContext) -> Result<(), DerpyError> {
+    for (name, mut sources) in find_all_vcs_info(&context.path)? {
+        let source = sources.remove(0);
+        context.log.event(Event::VcsDefinition {
+            name,
+            source,
+            shadowed: sources,
+        });
+    }
+    Ok(())
+}
+
+pub fn cli_vcs_dump(context: CommandContext) -> Result<(), DerpyError> {
+    let name = context.matches.value_of("name").unwrap().to_string();
+    let definition = match built_in_vcs_info(&name) {
+        Some(definition) => definition.to_string(),
+        None => return Err(DerpyError::NoBuiltInVcs { name }),
+    };
+    context.log.event(Event::BuiltInVcsDefinition { name, definition });
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use lockfile::{LockFile, load_lock, save_lock};
+use consts::{CONFIG_FILE, CONFIG_LOCK_FILE, VENDOR_MANIFEST_FILE};
+use resolve::walk_dependencies;
+use derpyfile::load_config;
+use overrides::OverrideFile;
+use std::path::Path;
+use path_utils::{ensure_dir, normalise_path, remove_dir};
+use cmds::CommandContext;
+use events::Event;
+use vcs::load_vcs_for;
+use patches::is_modified;
+use dependency::Dependency;
+use error::DerpyError;
+
+/// Exporting into a dependency, or replacing the directory it is in, would destroy what is being
+/// exported.
+fn check_vendor_dir(root: &Path, vendor_dir: &Path, dep: &Dependency) -> Result<(), DerpyError> {
+    let dep_dir = normalise_path(&dep.get_full_path_in(root));
+    if vendor_dir.starts_with(&dep_dir) || dep_dir.starts_with(vendor_dir) {
+        return Err(DerpyError::VendorDirOverlapsDependency {
+            path: vendor_dir.into(),
+            name: dep.name.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Exports every dependency, which must be at its locked version and unmodified, into a directory
+/// of its own in the vendor directory, then records what was exported in a manifest beside them.
+pub fn cli_vendor(context: CommandContext) -> Result<(), DerpyError> {
+    let config = load_config(context.path.join(CONFIG_FILE))?;
+    let lock = load_lock(context.path.join(CONFIG_LOCK_FILE))?;
+    let vendor_dir = normalise_path(&context.path.join(context.matches.value_of("dir").unwrap()));
+    for dep in config.dependencies.values() {
+        check_vendor_dir(&context.path, &vendor_dir, dep)?;
+    }
+    ensure_dir(&vendor_dir)?;
+
+    // overrides are ignored, as the vendored copy should be of what the lock file describes
+    let root = &context.path;
+    let offline = context.offline;
+    let manifest = Mutex::new(BTreeMap::new());
+    walk_dependencies(&context.log, root, &config, &OverrideFile::default(), 1, |log, dep| {
+        let name = &dep.name;
+        check_vendor_dir(root, &vendor_dir, dep)?;
+        let locked = match lock.dependencies.get(name) {
+            Some(locked) => locked,
+            None => return Err(DerpyError::NotLocked { name: name.clone() }),
+        };
+        let vcs = load_vcs_for(root, dep, offline)?;
+        if !dep.get_full_path_in(root).is_dir() || !vcs.get_checkout_dir(root, dep).is_dir() {
+            return Err(DerpyError::NotAtLockedVersion { name: name.clone() });
+        }
+        if vcs.get_version_of(log, root, dep)? != locked.revision {
+            return Err(DerpyError::NotAtLockedVersion { name: name.clone() });
+        }
+        if is_modified(vcs.as_ref(), log, root, dep)? == Some(true) {
+            return Err(DerpyError::DependencyModified { name: name.clone() });
+        }
+
+        let dest = vendor_dir.join(name);
+        if dest.exists() {
+            remove_dir(&dest)?;
+        }
+        vcs.export(log, root, dep, &dest)?;
+        log.event(Event::Vendored {
+            name: name.clone(),
+            revision: locked.revision.clone(),
+            path: dest,
+        });
+        manifest.lock().unwrap().insert(name.clone(), locked.clone());
+
+        Ok(())
+    })?;
+
+    let manifest_path = vendor_dir.join(VENDOR_MANIFEST_FILE);
+    save_lock(&LockFile { dependencies: manifest.into_inner().unwrap() }, &manifest_path)?;
+    context.log.event(Event::VendorManifestWritten { path: manifest_path });
+
+    Ok(())
+}
@@ -1,5 +1,14 @@
 
 pub const VCS_INFO_DIR: &str = "vcs_info/";
+pub const PROJECT_VCS_INFO_DIR: &str = ".derpy/vcs_info/";
+pub const USER_VCS_INFO_DIR: &str = "derpy/vcs_info/";
+pub const VCS_PATH_VAR: &str = "DERPY_VCS_PATH";
+pub const CACHE_DIR_VAR: &str = "DERPY_CACHE";
 pub const DEPENDENCY_DIR: &str = "deps/";
+pub const STAGING_DIR: &str = ".derpy/checkouts/";
+pub const PATCH_STATE_DIR: &str = ".derpy/patches/";
+pub const SOURCE_STATE_DIR: &str = ".derpy/sources/";
 pub const CONFIG_FILE: &str = "derpy.json";
 pub const CONFIG_LOCK_FILE: &str = "derpy.lock.json";
+pub const CONFIG_OVERRIDE_FILE: &str = "derpy.override.json";
+pub const VENDOR_MANIFEST_FILE: &str = "derpy.vendor.json";
@@ -1,5 +1,11 @@
 use std::collections::{BTreeMap, HashMap};
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+use sha2::{Digest, Sha256};
+use serde_json;
+use path_utils::{is_contained_path, normalise_path};
+use consts::STAGING_DIR;
+use error::DerpyError;
+use hooks::Hooks;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Dependency {
@@ -9,6 +15,22 @@ pub struct Dependency {
     pub version: String,
     pub target: String,
     pub options: BTreeMap<String, String>,
+    /// when given, only this directory within the repository is placed in the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<String>,
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    /// patch files, relative to the project declaring the dependency, applied in order on top of it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<String>,
+    /// whether the project lets this be placed outside of it, ignored for the dependencies of
+    /// dependencies, which never may be
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub allow_external: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 impl Dependency {
@@ -16,14 +38,109 @@ impl Dependency {
         PathBuf::from(&self.target).join(&self.name)
     }
 
+    /// whether the dependency would be placed somewhere inside the project directory
+    pub fn is_within_project(&self) -> bool {
+        is_contained_path(&self.get_full_path())
+    }
+
+    /// whether this dependency would be placed in the same directory as another
+    pub fn shares_path_with(&self, other: &Dependency) -> bool {
+        normalise_path(&self.get_full_path()) == normalise_path(&other.get_full_path())
+    }
+
+    /// the directory the dependency is placed in, for the project at `root`
+    pub fn get_target_dir_in(&self, root: &Path) -> PathBuf {
+        root.join(&self.target)
+    }
+
+    /// the dependency's own directory, for the project at `root`
+    pub fn get_full_path_in(&self, root: &Path) -> PathBuf {
+        root.join(self.get_full_path())
+    }
+
+    /// The dependency as required by the project placed in `dir`, relative to this one, with the
+    /// paths of its patches, and the directory of a path dependency, made relative to this project
+    /// rather than the one that declared them.
+    pub fn declared_in(self, dir: &Path) -> Dependency {
+        let rebase = |path: &str| normalise_path(&dir.join(path)).to_string_lossy().into_owned();
+        let patches = self.patches.iter().map(|patch| rebase(patch)).collect();
+        let source = self.url.strip_prefix("file://").unwrap_or(&self.url);
+        let url = if self.vcs == "path" && Path::new(source).is_relative() {
+            rebase(source)
+        } else {
+            self.url.clone()
+        };
+        Dependency { patches, url, ..self }
+    }
+
+    /// Fails unless the name is a single ordinary path component, since it is used as a directory
+    /// name both where the dependency is placed and in derpy's own state.
+    pub fn validate_name(&self) -> Result<(), DerpyError> {
+        let mut components = Path::new(&self.name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => Ok(()),
+            _ => Err(DerpyError::InvalidDependencyName { name: self.name.clone() }),
+        }
+    }
+
+    /// fails unless the subdirectory wanted, if any, is somewhere inside the repository
+    pub fn validate_subdir(&self) -> Result<(), DerpyError> {
+        match self.subdir {
+            Some(ref subdir) if subdir.is_empty() || !is_contained_path(Path::new(subdir)) => {
+                Err(DerpyError::InvalidSubdir {
+                    name: self.name.clone(),
+                    subdir: subdir.clone(),
+                })
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// The directory the dependency's VCS keeps it in, for the project at `root`. This is the
+    /// dependency's own directory, unless only a subdirectory of it is wanted, in which case it is
+    /// kept in a staging area and the subdirectory is copied into place from there.
+    pub fn get_checkout_dir_in(&self, root: &Path) -> PathBuf {
+        match self.subdir {
+            Some(_) => root.join(STAGING_DIR).join(&self.name),
+            None => self.get_full_path_in(root),
+        }
+    }
+
+    /// the directory the dependency's VCS checks it out into, for the project at `root`
+    pub fn get_checkout_parent_in(&self, root: &Path) -> PathBuf {
+        match self.subdir {
+            Some(_) => root.join(STAGING_DIR),
+            None => self.get_target_dir_in(root),
+        }
+    }
+
     pub fn build_macro_map(&self) -> HashMap<String, String> {
         let mut result = HashMap::new();
         result.insert("DEP_NAME".into(), self.name.clone());
         result.insert("DEP_URL".into(), self.url.clone());
         result.insert("DEP_VERSION".into(), self.version.clone());
+        if let Some(ref subdir) = self.subdir {
+            result.insert("DEP_SUBDIR".into(), subdir.clone());
+        }
         for (key, value) in self.options.iter() {
             result.insert(format!("DEP_OPT_{}", key), value.clone());
         }
         result
     }
+
+    /// A hash of this entry as written in a config file, used to spot when it has been edited.
+    /// Hooks and patches are left out, as changing them doesn't change what is acquired (patches
+    /// are tracked separately, see `patches::update_patches`).
+    pub fn config_hash(&self) -> String {
+        let entry = Dependency {
+            hooks: Hooks::default(),
+            patches: Vec::new(),
+            ..self.clone()
+        };
+        let encoded = serde_json::to_string(&entry)
+            .expect("dependencies are always representable as json");
+        Sha256::digest(encoded.as_bytes()).iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
 }
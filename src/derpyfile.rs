@@ -7,19 +7,11 @@ use serde_json;
 use dependency::Dependency;
 use error::DerpyError;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct DerpyFile {
     pub dependencies: BTreeMap<String, Dependency>,
 }
 
-impl Default for DerpyFile {
-    fn default() -> Self {
-        Self {
-            dependencies: BTreeMap::new(),
-        }
-    }
-}
-
 pub fn load_config<P: AsRef<Path>>(path: P) -> Result<DerpyFile, DerpyError> {
     let mut contents = String::new();
     let mut file = match File::open(path) {
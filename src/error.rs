@@ -1,13 +1,21 @@
 use subprocess::{ExitStatus, PopenError};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use strfmt::FmtError;
+use serde::{Serialize, Serializer};
+use serde::ser::{Error, SerializeStruct};
 use serde_json;
+use std::borrow::Borrow;
+use std::fmt;
 use std::io;
 
+use resolve::ConflictingRequirement;
 use acquire::AcquireMode;
 use vcs::VcsCommand;
 
-#[derive(Fail, Debug)]
+/// Serialized as its kind and fields, see `serialize_with_message` for reporting it.
+#[derive(Fail, Debug, Serialize)]
+#[serde(tag = "kind")]
 pub enum DerpyError {
     #[fail(display = "invalid arguments: {}", reason)]
     InvalidArguments {
@@ -19,13 +27,73 @@ pub enum DerpyError {
     DependencyAlreadyExists {
         name: String,
     },
+    #[fail(display = "dependency '{}' would be placed outside the project at {:?}", name, path)]
+    DependencyOutsideProject {
+        name: String,
+        path: PathBuf,
+    },
+    #[fail(display = "dependencies '{}' and '{}' would both be placed at {:?}", name, other_name, path)]
+    DependencyPathClash {
+        name: String,
+        other_name: String,
+        path: PathBuf,
+    },
+    #[fail(display = "invalid dependency name {:?}, it must be a single directory name", name)]
+    InvalidDependencyName {
+        name: String,
+    },
+    #[fail(display = "subdirectory '{}' of dependency '{}' must be a relative path inside the repository", subdir, name)]
+    InvalidSubdir {
+        name: String,
+        subdir: String,
+    },
+    #[fail(display = "dependency '{}' has no subdirectory {:?}", name, path)]
+    MissingSubdir {
+        name: String,
+        path: PathBuf,
+    },
+    #[fail(display = "dependency '{}' has an invalid version range {:?}: {}", name, range, error)]
+    InvalidVersionRange {
+        name: String,
+        range: String,
+        error: String,
+    },
+    #[fail(display = "dependency '{}' has no tag matching version range {}", name, range)]
+    NoMatchingVersion {
+        name: String,
+        range: String,
+    },
+    #[fail(display = "dependency '{}' has a version range, but {} has no way to list its versions", name, vcs)]
+    UnableToListVersions {
+        name: String,
+        vcs: String,
+    },
+    #[fail(display = "dependency '{}' not found", name)]
+    UnknownDependency {
+        name: String,
+    },
+    #[fail(display = "dependency '{}' has local modifications", name)]
+    DependencyModified {
+        name: String,
+    },
+    #[fail(display = "unable to tell whether dependency '{}' has local modifications, {} has no is_dirty command", name, vcs)]
+    UnableToCheckForModifications {
+        name: String,
+        vcs: String,
+    },
     #[fail(display = "version control system '{}' unknown", name)]
     UnknownVcs {
         name: String,
     },
-    #[fail(display = "vcs command {:?} returned {:?}, stdout='{}', stderr='{}'", cmd, return_code, stdout, stderr)]
+    #[fail(display = "there is no built in definition for version control system '{}'", name)]
+    NoBuiltInVcs {
+        name: String,
+    },
+    #[fail(display = "vcs command {:?} in {:?} returned {:?}, stdout='{}', stderr='{}'", cmd, dir, return_code, stdout, stderr)]
     VcsCommandFailed {
         cmd: VcsCommand,
+        dir: PathBuf,
+        #[serde(serialize_with = "serialize_debug")]
         return_code: ExitStatus,
         stdout: String,
         stderr: String,
@@ -35,67 +103,389 @@ pub enum DerpyError {
         dependency: String,
         acquire_mode: AcquireMode,
     },
+    #[fail(display = "dependency cycle detected: {:?}", cycle)]
+    DependencyCycle {
+        cycle: Vec<String>,
+    },
+    #[fail(display = "conflicting requirements for dependency '{}': {} but {}", name, first, second)]
+    DependencyConflict {
+        name: String,
+        first: Box<ConflictingRequirement>,
+        second: Box<ConflictingRequirement>,
+    },
+    #[fail(display = "{} hook of dependency '{}' failed: {}", hook, name, error)]
+    HookFailed {
+        name: String,
+        hook: String,
+        #[serde(serialize_with = "serialize_with_message")]
+        error: Box<DerpyError>,
+    },
+    #[fail(display = "patch {} no longer applies to dependency '{}':\n{}", patch, name, output)]
+    PatchDoesNotApply {
+        name: String,
+        patch: String,
+        output: String,
+    },
+    #[fail(display = "unable to revert patch {} from dependency '{}', has it been modified?\n{}", patch, name, output)]
+    UnableToRevertPatch {
+        name: String,
+        patch: String,
+        output: String,
+    },
+    #[fail(display = "unable to read patch {} for dependency '{}': {}", patch, name, error)]
+    UnableToReadPatch {
+        name: String,
+        patch: String,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "patches can't be applied to path dependency '{}', as it would change the original directory", name)]
+    PatchedPathDependency {
+        name: String,
+    },
+    #[fail(display = "unable to read applied patches {:?}: {}", path, error)]
+    UnableToReadPatchState {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to decode applied patches {:?}: {}", path, error)]
+    UnableToDecodePatchState {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: serde_json::Error,
+    },
+    #[fail(display = "unable to write applied patches {:?}: {}", path, error)]
+    UnableToWritePatchState {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to read checkout source {:?}: {}", path, error)]
+    UnableToReadCheckoutSource {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to decode checkout source {:?}: {}", path, error)]
+    UnableToDecodeCheckoutSource {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: serde_json::Error,
+    },
+    #[fail(display = "unable to lock mirror {:?}: {}", path, error)]
+    UnableToLockMirror {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to write checkout source {:?}: {}", path, error)]
+    UnableToWriteCheckoutSource {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "dependency '{}' is now acquired from {} rather than {}, but its checkout has local modifications, commit or discard them first", name, to_url, from_url)]
+    MovedDependencyModified {
+        name: String,
+        from_url: String,
+        to_url: String,
+    },
+    #[fail(display = "vendor directory {:?} overlaps dependency '{}', choose a directory outside of it", path, name)]
+    VendorDirOverlapsDependency {
+        path: PathBuf,
+        name: String,
+    },
+    #[fail(display = "version {} of dependency '{}' is ambiguous upstream, it could be any of {:?}", version, name, refs)]
+    AmbiguousRemoteVersion {
+        name: String,
+        version: String,
+        refs: Vec<String>,
+    },
     #[fail(display = "failed to expand macros: {} (source text: {}, macros: {:?})", error, source_text, macros)]
     MacroExpansionFailure {
         source_text: String,
         macros: HashMap<String, String>,
+        #[serde(serialize_with = "serialize_display")]
         error: FmtError,
     },
-    #[fail(display = "error invoking subprocess: {} (command: {:?})", error, cmd)]
+    #[fail(display = "error invoking subprocess: {} (command: {:?}, in: {:?})", error, cmd, dir)]
     SubprocessError {
         cmd: VcsCommand,
+        dir: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
         error: PopenError,
     },
+    #[fail(display = "unable to run vcs command {:?}, directory {:?} does not exist", cmd, dir)]
+    MissingWorkingDirectory {
+        cmd: VcsCommand,
+        dir: PathBuf,
+    },
     #[fail(display = "unable to determine current directory: {:?}", error)]
     UnableToDetermineCurrentDir {
-        error: io::Error,
-    },
-    #[fail(display = "unable to change current directory: {:?}", error)]
-    UnableToChangeDir {
+        #[serde(serialize_with = "serialize_display")]
         error: io::Error,
     },
     #[fail(display = "unable to determine current exe path: {:?}", error)]
     UnableToDetermineCurrentExePath {
+        #[serde(serialize_with = "serialize_display")]
         error: io::Error,
     },
     #[fail(display = "failed to create directory: {:?}", error)]
     FailedToCreateDirectory {
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to read archive {:?}: {}", path, error)]
+    UnableToReadArchive {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to extract archive {:?}: {}", path, error)]
+    UnableToExtractArchive {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to tell what kind of archive {:?} is, expected a .tar.gz, .tar.xz or .zip file", path)]
+    UnknownArchiveFormat {
+        path: PathBuf,
+    },
+    #[fail(display = "archive for '{}' has sha256 {} but {} was expected", name, actual, expected)]
+    ArchiveChecksumMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    #[fail(display = "directory {:?} for path dependency '{}' does not exist", path, name)]
+    MissingPathDependency {
+        name: String,
+        path: PathBuf,
+    },
+    #[fail(display = "path dependency '{}' is now at version {} rather than its locked version {}, upgrade it to use the new version", name, actual, expected)]
+    PathDependencyChanged {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    #[fail(display = "'{}' isn't available offline: {}", name, reason)]
+    UnavailableOffline {
+        name: String,
+        reason: String,
+    },
+    #[fail(display = "unable to continue offline, these dependencies aren't available locally:{}", _0)]
+    MissingOffline(MissingDependencies),
+    #[fail(display = "dependency '{}' isn't locked, run derpy acquire first", name)]
+    NotLocked {
+        name: String,
+    },
+    #[fail(display = "dependency '{}' isn't at its locked version, run derpy acquire first", name)]
+    NotAtLockedVersion {
+        name: String,
+    },
+    #[fail(display = "dependency '{}' isn't vendored in {:?}", name, dir)]
+    NotVendored {
+        name: String,
+        dir: PathBuf,
+    },
+    #[fail(display = "dependency '{}' is locked at version {} but was vendored at version {}", name, locked_version, vendored_version)]
+    VendoredVersionMismatch {
+        name: String,
+        locked_version: String,
+        vendored_version: String,
+    },
+    #[fail(display = "dependency '{}' was restored from a vendored copy and can't be changed, delete it to acquire it again", name)]
+    VendoredDependencyFixed {
+        name: String,
+    },
+    #[fail(display = "unable to read vendored revision {:?}: {}", path, error)]
+    UnableToReadVendoredRevision {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to write vendored revision {:?}: {}", path, error)]
+    UnableToWriteVendoredRevision {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "{:?} is in the way of dependency '{}', move or delete it first", path, name)]
+    DependencyInTheWay {
+        name: String,
+        path: PathBuf,
+    },
+    #[fail(display = "unable to create symlink {:?}: {}", path, error)]
+    UnableToLinkDirectory {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to copy directory {:?}: {}", path, error)]
+    UnableToCopyDirectory {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to hash the contents of {:?}: {}", path, error)]
+    UnableToHashDirectory {
+        path: PathBuf,
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "dependency '{}' has invalid value '{}' for option '{}'", name, value, option)]
+    InvalidDependencyOption {
+        name: String,
+        option: String,
+        value: String,
+    },
+    #[fail(display = "failed to remove directory: {:?}", error)]
+    FailedToRemoveDirectory {
+        #[serde(serialize_with = "serialize_display")]
         error: io::Error,
     },
     #[fail(display = "unable to open VCS info file: {:?}", error)]
     UnableToOpenVcsInfo {
+        #[serde(serialize_with = "serialize_display")]
         error: io::Error,
     },
     #[fail(display = "unable to read VCS info file: {:?}", error)]
     UnableToReadVcsInfo {
+        #[serde(serialize_with = "serialize_display")]
         error: io::Error,
     },
     #[fail(display = "unable to decode VCS info file: {:?}", error)]
     UnableToDecodeVcsInfo {
+        #[serde(serialize_with = "serialize_display")]
         error: serde_json::Error,
     },
     #[fail(display = "unable to open config file: {:?}", error)]
     UnableToOpenConfig {
+        #[serde(serialize_with = "serialize_display")]
         error: io::Error,
     },
     #[fail(display = "unable to read config file: {:?}", error)]
     UnableToReadConfig {
+        #[serde(serialize_with = "serialize_display")]
         error: io::Error,
     },
     #[fail(display = "unable to decode config file: {:?}", error)]
     UnableToDecodeConfig {
+        #[serde(serialize_with = "serialize_display")]
         error: serde_json::Error,
     },
     #[fail(display = "unable to create config file: {:?}", error)]
     UnableToCreateConfig {
+        #[serde(serialize_with = "serialize_display")]
         error: io::Error,
     },
     #[fail(display = "unable to encode config file: {:?}", error)]
     UnableToEncodeConfig {
+        #[serde(serialize_with = "serialize_display")]
         error: serde_json::Error,
     },
     #[fail(display = "unable to write to config file: {:?}", error)]
     UnableToWriteConfig {
+        #[serde(serialize_with = "serialize_display")]
         error: io::Error,
     },
+    #[fail(display = "unable to open lock file: {:?}", error)]
+    UnableToOpenLockFile {
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to read lock file: {:?}", error)]
+    UnableToReadLockFile {
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to decode lock file: {:?}", error)]
+    UnableToDecodeLockFile {
+        #[serde(serialize_with = "serialize_display")]
+        error: serde_json::Error,
+    },
+    #[fail(display = "unable to create lock file: {:?}", error)]
+    UnableToCreateLockFile {
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to encode lock file: {:?}", error)]
+    UnableToEncodeLockFile {
+        #[serde(serialize_with = "serialize_display")]
+        error: serde_json::Error,
+    },
+    #[fail(display = "unable to write to lock file: {:?}", error)]
+    UnableToWriteLockFile {
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to open override file: {:?}", error)]
+    UnableToOpenOverrideFile {
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to read override file: {:?}", error)]
+    UnableToReadOverrideFile {
+        #[serde(serialize_with = "serialize_display")]
+        error: io::Error,
+    },
+    #[fail(display = "unable to decode override file: {:?}", error)]
+    UnableToDecodeOverrideFile {
+        #[serde(serialize_with = "serialize_display")]
+        error: serde_json::Error,
+    },
+    #[fail(display = "invalid override for '{}': {}", name, reason)]
+    InvalidOverride {
+        name: String,
+        reason: String,
+    },
+}
+
+/// the dependencies that couldn't be acquired offline, with the reason for each
+#[derive(Debug)]
+pub struct MissingDependencies(pub Vec<(String, String)>);
+
+impl Serialize for MissingDependencies {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Missing<'a> {
+            name: &'a str,
+            reason: &'a str,
+        }
+
+        let mut state = serializer.serialize_struct("MissingDependencies", 1)?;
+        let missing = self.0.iter()
+            .map(|(name, reason)| Missing { name, reason })
+            .collect::<Vec<_>>();
+        state.serialize_field("dependencies", &missing)?;
+        state.end()
+    }
+}
+
+impl fmt::Display for MissingDependencies {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, reason) in self.0.iter() {
+            write!(f, "\n  - '{}': {}", name, reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// fields that can't be serialized themselves are given as their message
+fn serialize_display<T: fmt::Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// the same, for fields that only have a debug representation
+fn serialize_debug<T: fmt::Debug, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{:?}", value))
+}
+
+/// Serializes an error's kind and fields along with its message, as it is reported.
+pub fn serialize_with_message<E: Borrow<DerpyError>, S: Serializer>(error: &E, serializer: S) -> Result<S::Ok, S::Error> {
+    let error = error.borrow();
+    let mut value = serde_json::to_value(error).map_err(S::Error::custom)?;
+    value["message"] = error.to_string().into();
+    value.serialize(serializer)
 }
@@ -0,0 +1,332 @@
+use std::path::PathBuf;
+use std::fmt;
+use acquire::{AcquireOutcome, PlannedAction};
+use consts::CONFIG_OVERRIDE_FILE;
+use error::{serialize_with_message, DerpyError};
+use vcs::{VcsCommand, VcsInfoSource};
+
+/// Describes the state of a dependency on disk relative to the lock file.
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DependencyStatus {
+    Missing {
+        locked_version: Option<String>,
+    },
+    Unlocked {
+        current_version: String,
+    },
+    AtLockedVersion {
+        current_version: String,
+    },
+    AtOtherVersion {
+        current_version: String,
+        locked_version: String,
+    },
+}
+
+/// How a dependency's lock file entry would change, as part of a dry run.
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LockChange {
+    /// `revision` is `None` when it won't be known until the VCS commands have run
+    Added {
+        revision: Option<String>,
+    },
+    Updated {
+        from_revision: String,
+        to_revision: Option<String>,
+    },
+}
+
+/// A version of a dependency found upstream, as a tag and the revision it is at, or just a revision.
+#[derive(Serialize, Clone)]
+pub struct UpstreamVersion {
+    pub tag: Option<String>,
+    pub revision: Option<String>,
+}
+
+impl fmt::Display for UpstreamVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.tag, &self.revision) {
+            (Some(tag), Some(revision)) => write!(f, "{} ({})", tag, revision),
+            (Some(tag), None) => write!(f, "{}", tag),
+            (None, Some(revision)) => write!(f, "{}", revision),
+            (None, None) => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Everything derpy reports to the user, printed either as text or as one JSON object per line.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Acquire {
+        name: String,
+        outcome: AcquireOutcome,
+        commands: Vec<VcsCommand>,
+    },
+    Overridden {
+        name: String,
+    },
+    Planned {
+        name: String,
+        actions: Vec<PlannedAction>,
+        lock_change: Option<LockChange>,
+    },
+    LockEntryChanged {
+        name: String,
+        locked_version: String,
+    },
+    VersionResolved {
+        name: String,
+        range: String,
+        tag: String,
+    },
+    RequirementOverridden {
+        name: String,
+        requester: String,
+        version: String,
+        url: String,
+        chosen_version: String,
+        chosen_url: String,
+    },
+    NoLongerRequired {
+        name: String,
+    },
+    CheckoutReplaced {
+        name: String,
+        from_url: String,
+        to_url: String,
+    },
+    LockFileUpdated,
+    Removed {
+        name: String,
+    },
+    Deleted {
+        path: PathBuf,
+    },
+    Status {
+        name: String,
+        status: DependencyStatus,
+        local_modifications: Option<bool>,
+        changed_since_locked: bool,
+        overridden: bool,
+    },
+    Unreferenced {
+        path: PathBuf,
+    },
+    Outdated {
+        name: String,
+        locked_revision: Option<String>,
+        locked_tag: Option<String>,
+        /// the newest version on the dependency's branch, or within its version range
+        latest_compatible: Option<UpstreamVersion>,
+        /// the newest version of all, which only differs for version ranges
+        latest: Option<UpstreamVersion>,
+        overridden: bool,
+    },
+    Vendored {
+        name: String,
+        revision: String,
+        path: PathBuf,
+    },
+    VendorManifestWritten {
+        path: PathBuf,
+    },
+    MirrorUpdateFailed {
+        url: String,
+        #[serde(serialize_with = "serialize_with_message")]
+        error: DerpyError,
+    },
+    VcsNotFound {
+        vcs: String,
+    },
+    VcsDefinition {
+        name: String,
+        source: VcsInfoSource,
+        shadowed: Vec<VcsInfoSource>,
+    },
+    BuiltInVcsDefinition {
+        name: String,
+        definition: String,
+    },
+    Error {
+        #[serde(serialize_with = "serialize_with_message")]
+        error: DerpyError,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Event::Acquire { ref name, ref outcome, .. } => match *outcome {
+                AcquireOutcome::Acquired { ref at_version } => {
+                    write!(f, "- acquired '{}' at version {}", name, at_version)
+                },
+                AcquireOutcome::Restored { ref from_version, ref to_version } => {
+                    write!(f, "- restored '{}' to {} from {}", name, to_version, from_version)
+                },
+                AcquireOutcome::UpgradedTo { ref from_version, ref to_version } => {
+                    write!(f, "- upgraded '{}' to {} from {}", name, to_version, from_version)
+                },
+                AcquireOutcome::NoChange { ref current_version } => {
+                    write!(f, "- '{}' up to date at version {}", name, current_version)
+                },
+                AcquireOutcome::Ignored { ref at_version } => {
+                    writeln!(f, "- warning: ignored '{}' - left at version {}", name, at_version)?;
+                    write!(f, "  (dependency {} present but has no lock file entry)", name)
+                },
+            },
+            Event::Planned { ref name, ref actions, ref lock_change } => {
+                if actions.is_empty() {
+                    write!(f, "- '{}' would be left as it is", name)?;
+                } else {
+                    write!(f, "- plan for '{}':", name)?;
+                }
+                for action in actions {
+                    let commands = match *action {
+                        PlannedAction::Acquire { ref commands } => {
+                            write!(f, "\n  acquire")?;
+                            commands.as_ref()
+                        },
+                        PlannedAction::Checkout { ref to_version, ref commands } => {
+                            write!(f, "\n  check out version {}", to_version)?;
+                            commands.as_ref()
+                        },
+                        PlannedAction::Upgrade { ref commands } => {
+                            write!(f, "\n  upgrade")?;
+                            commands.as_ref()
+                        },
+                        PlannedAction::ApplyPatches { ref patches } => {
+                            write!(f, "\n  apply patches {}", patches.join(", "))?;
+                            continue;
+                        },
+                        PlannedAction::RunHook { ref hook, ref commands } => {
+                            write!(f, "\n  run {} hook", hook)?;
+                            Some(commands)
+                        },
+                    };
+                    match commands {
+                        Some(commands) => for command in commands {
+                            write!(f, "\n    $ {}", command.join(" "))?;
+                        },
+                        None => write!(f, " (done by derpy itself)")?,
+                    }
+                }
+                match *lock_change {
+                    Some(LockChange::Added { revision: Some(ref revision) }) => {
+                        write!(f, "\n  (would be locked at version {})", revision)
+                    },
+                    Some(LockChange::Added { revision: None }) => {
+                        write!(f, "\n  (would be locked at whichever version it ends up at)")
+                    },
+                    Some(LockChange::Updated { ref from_revision, to_revision: Some(ref to_revision) }) => {
+                        write!(f, "\n  (lock entry would change from version {} to {})", from_revision, to_revision)
+                    },
+                    Some(LockChange::Updated { ref from_revision, to_revision: None }) => {
+                        write!(f, "\n  (lock entry at version {} may change to whichever version it ends up at)", from_revision)
+                    },
+                    None => Ok(()),
+                }
+            },
+            Event::Overridden { ref name } => {
+                write!(f, "- '{}' overridden by {}, its lock file entry is left as it is", name, CONFIG_OVERRIDE_FILE)
+            },
+            Event::LockEntryChanged { ref name, ref locked_version } => {
+                write!(f, "- warning: '{}' changed since it was locked, ignoring locked version {}", name, locked_version)
+            },
+            Event::VersionResolved { ref name, ref range, ref tag } => {
+                write!(f, "- resolved '{}' version {} to tag {}", name, range, tag)
+            },
+            Event::RequirementOverridden { ref name, ref requester, ref version, ref url, ref chosen_version, ref chosen_url } => {
+                write!(f, "warning: {} requires '{}' at {} ({}), using the project's choice of {} ({})",
+                    requester, name, version, url, chosen_version, chosen_url)
+            },
+            Event::NoLongerRequired { ref name } => write!(f, "- '{}' is no longer required", name),
+            Event::CheckoutReplaced { ref name, ref from_url, ref to_url } => {
+                write!(f, "- '{}' is now acquired from {} rather than {}, replacing its checkout", name, to_url, from_url)
+            },
+            Event::LockFileUpdated => write!(f, "lock file updated"),
+            Event::Removed { ref name } => write!(f, "- removed '{}'", name),
+            Event::Deleted { ref path } => write!(f, "- deleted {:?}", path),
+            Event::Status { ref name, ref status, local_modifications, changed_since_locked, overridden } => {
+                match *status {
+                    DependencyStatus::Missing { locked_version: Some(ref locked_version) } => {
+                        write!(f, "- '{}' missing, locked at version {}", name, locked_version)?;
+                    },
+                    DependencyStatus::Missing { locked_version: None } => {
+                        write!(f, "- '{}' missing, not locked", name)?;
+                    },
+                    DependencyStatus::Unlocked { ref current_version } => {
+                        write!(f, "- '{}' at version {}, not locked", name, current_version)?;
+                    },
+                    DependencyStatus::AtLockedVersion { ref current_version } => {
+                        write!(f, "- '{}' at locked version {}", name, current_version)?;
+                    },
+                    DependencyStatus::AtOtherVersion { ref current_version, ref locked_version } => {
+                        write!(f, "- '{}' at version {} but locked at version {}", name, current_version, locked_version)?;
+                    },
+                }
+                if local_modifications == Some(true) {
+                    write!(f, "\n  (dependency {} has local modifications)", name)?;
+                }
+                if changed_since_locked {
+                    write!(f, "\n  (dependency {} changed since it was locked)", name)?;
+                }
+                if overridden {
+                    write!(f, "\n  (dependency {} is overridden by {})", name, CONFIG_OVERRIDE_FILE)?;
+                }
+                Ok(())
+            },
+            Event::Unreferenced { ref path } => {
+                write!(f, "- warning: {:?} is not referenced by any dependency", path)
+            },
+            Event::Outdated { ref name, ref locked_revision, ref locked_tag, ref latest_compatible, ref latest, overridden } => {
+                match (locked_revision, locked_tag) {
+                    (Some(revision), Some(tag)) => write!(f, "- '{}' locked at {} ({})", name, tag, revision)?,
+                    (Some(revision), None) => write!(f, "- '{}' locked at {}", name, revision)?,
+                    (None, _) => write!(f, "- '{}' not locked", name)?,
+                }
+                let is_locked_at = |version: &Option<UpstreamVersion>| match version {
+                    Some(version) => version.revision.is_some() && version.revision == *locked_revision,
+                    None => false,
+                };
+                match (latest_compatible, latest) {
+                    (None, None) => write!(f, ", unable to ask upstream for newer versions")?,
+                    _ if is_locked_at(latest_compatible) && is_locked_at(latest) => write!(f, ", up to date")?,
+                    _ => {
+                        if let Some(latest_compatible) = latest_compatible {
+                            write!(f, ", latest compatible {}", latest_compatible)?;
+                        }
+                        if let Some(latest) = latest {
+                            write!(f, ", latest {}", latest)?;
+                        }
+                    },
+                }
+                if overridden {
+                    write!(f, "\n  (dependency {} is overridden by {})", name, CONFIG_OVERRIDE_FILE)?;
+                }
+                Ok(())
+            },
+            Event::Vendored { ref name, ref revision, ref path } => {
+                write!(f, "- vendored '{}' at version {} into {:?}", name, revision, path)
+            },
+            Event::VendorManifestWritten { ref path } => write!(f, "vendor manifest written to {:?}", path),
+            Event::MirrorUpdateFailed { ref url, ref error } => {
+                write!(f, "warning: unable to update the cached mirror of {}, using it as it is: {}", url, error)
+            },
+            Event::VcsNotFound { ref vcs } => {
+                write!(f, "warning: unable to determine version of {}, is it installed?", vcs)
+            },
+            Event::VcsDefinition { ref name, ref source, ref shadowed } => {
+                write!(f, "- {}: {}", name, source)?;
+                for shadowed_source in shadowed {
+                    write!(f, "\n  (overrides {})", shadowed_source)?;
+                }
+                Ok(())
+            },
+            Event::BuiltInVcsDefinition { ref definition, .. } => write!(f, "{}", definition.trim_end()),
+            Event::Error { ref error } => write!(f, "error: {}", error),
+        }
+    }
+}
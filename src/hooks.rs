@@ -0,0 +1,69 @@
+use std::path::Path;
+use acquire::{AcquireOutcome, PlannedAction};
+use dependency::Dependency;
+use error::DerpyError;
+use vcs::{expand_vcs_command_list, run_cmd_sequence, VcsCommandList};
+use log::Log;
+
+/// Commands run in a dependency's directory after derpy changes it, e.g. to generate a config
+/// header or build a library. They can use the same macros as VCS commands, as well as
+/// `{DEP_REVISION}` for the revision the dependency is now at.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Hooks {
+    /// run after the dependency is acquired for the first time
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_acquire: VcsCommandList,
+    /// run after the dependency is upgraded to a new revision
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_upgrade: VcsCommandList,
+    /// run after an existing checkout of the dependency is restored to its locked revision
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_checkout: VcsCommandList,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.post_acquire.is_empty() && self.post_upgrade.is_empty() && self.post_checkout.is_empty()
+    }
+}
+
+/// runs whichever of the dependency's hooks applies to what acquiring it did, if any
+pub fn run_hooks(log: &Log, root: &Path, dep: &Dependency, outcome: &AcquireOutcome) -> Result<(), DerpyError> {
+    let (hook, commands, revision) = match *outcome {
+        AcquireOutcome::Acquired { ref at_version } => ("post_acquire", &dep.hooks.post_acquire, at_version),
+        AcquireOutcome::UpgradedTo { ref to_version, .. } => ("post_upgrade", &dep.hooks.post_upgrade, to_version),
+        AcquireOutcome::Restored { ref to_version, .. } => ("post_checkout", &dep.hooks.post_checkout, to_version),
+        AcquireOutcome::NoChange { .. } | AcquireOutcome::Ignored { .. } => return Ok(()),
+    };
+    if commands.is_empty() {
+        return Ok(());
+    }
+
+    log.info(format!("running {} hook for '{}'", hook, dep.name));
+    let mut macros = dep.build_macro_map();
+    macros.insert("DEP_REVISION".into(), revision.clone());
+    let result = expand_vcs_command_list(commands, &macros)
+        .and_then(|cmd| run_cmd_sequence(log, &cmd, &dep.get_full_path_in(root)));
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) => Err(DerpyError::HookFailed {
+            name: dep.name.clone(),
+            hook: hook.into(),
+            error: Box::new(e),
+        }),
+    }
+}
+
+/// the action running one of the dependency's hooks would be, if it has any commands, with
+/// `{DEP_REVISION}` left in place when the revision isn't known yet
+pub fn plan_hook(dep: &Dependency, hook: &str, commands: &VcsCommandList, revision: Option<&str>) -> Result<Option<PlannedAction>, DerpyError> {
+    if commands.is_empty() {
+        return Ok(None);
+    }
+    let mut macros = dep.build_macro_map();
+    macros.insert("DEP_REVISION".into(), revision.unwrap_or("{DEP_REVISION}").into());
+    Ok(Some(PlannedAction::RunHook {
+        hook: hook.into(),
+        commands: expand_vcs_command_list(commands, &macros)?,
+    }))
+}
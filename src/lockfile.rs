@@ -0,0 +1,114 @@
+use std::fs::{File, OpenOptions};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use serde_json;
+
+use resolve::{Requester, ResolvedDependency};
+use dependency::Dependency;
+use patches::LockedPatch;
+use error::DerpyError;
+
+/// A dependency as it was resolved the last time it was acquired or upgraded.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct LockedDependency {
+    pub name: String,
+    pub vcs: String,
+    pub url: String,
+    /// the exact revision acquired, older lock files called this the version
+    #[serde(alias = "version")]
+    pub revision: String,
+    /// the tag a version range was resolved to, if the dependency asked for a range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    pub target: String,
+    /// the only directory within the repository placed in the project, if not all of it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<String>,
+    /// the patches applied on top of the revision
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<LockedPatch>,
+    #[serde(default)]
+    pub required_by: Vec<Requester>,
+    /// hash of the config entry this was resolved from, missing from older lock files
+    #[serde(default)]
+    pub config_hash: Option<String>,
+}
+
+impl LockedDependency {
+    pub fn new(resolved: &ResolvedDependency, revision: String, tag: Option<String>, patches: Vec<LockedPatch>) -> Self {
+        let dep = &resolved.dependency;
+        Self {
+            name: dep.name.clone(),
+            vcs: dep.vcs.clone(),
+            url: dep.url.clone(),
+            revision,
+            tag,
+            target: dep.target.clone(),
+            subdir: dep.subdir.clone(),
+            patches,
+            required_by: resolved.required_by.clone(),
+            config_hash: Some(dep.config_hash()),
+        }
+    }
+
+    /// whether this entry was locked from the config entry given, as it is now
+    pub fn is_locked_from(&self, dep: &Dependency) -> bool {
+        match self.config_hash {
+            Some(ref hash) => *hash == dep.config_hash(),
+            None => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq)]
+pub struct LockFile {
+    pub dependencies: BTreeMap<String, LockedDependency>,
+}
+
+pub fn load_lock<P: AsRef<Path>>(path: P) -> Result<LockFile, DerpyError> {
+    let mut contents = String::new();
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => return Err(DerpyError::UnableToOpenLockFile {
+            error: e,
+        }),
+    };
+    if let Err(e) = file.read_to_string(&mut contents) {
+        return Err(DerpyError::UnableToReadLockFile {
+            error: e,
+        });
+    }
+    match serde_json::from_str(&contents) {
+        Ok(lock) => Ok(lock),
+        Err(e) => Err(DerpyError::UnableToDecodeLockFile {
+            error: e,
+        })
+    }
+}
+
+pub fn save_lock<P: AsRef<Path>>(lock: &LockFile, path: P) -> Result<(), DerpyError> {
+    let file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open(path);
+    let mut file = match file {
+        Ok(file) => file,
+        Err(e) => return Err(DerpyError::UnableToCreateLockFile {
+            error: e,
+        }),
+    };
+    let contents = match serde_json::to_string_pretty(lock) {
+        Ok(contents) => contents,
+        Err(e) => return Err(DerpyError::UnableToEncodeLockFile {
+            error: e,
+        }),
+    };
+    match file.write_all(contents.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(DerpyError::UnableToWriteLockFile {
+            error: e,
+        }),
+    }
+}
@@ -1,3 +1,7 @@
+use std::cell::RefCell;
+use serde_json;
+use events::Event;
+use vcs::VcsCommand;
 
 #[derive(Copy, Clone, Debug)]
 pub enum Verbosity {
@@ -6,27 +10,86 @@ pub enum Verbosity {
     Verbose,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl<'a> From<Option<&'a str>> for OutputFormat {
+    fn from(other: Option<&'a str>) -> OutputFormat {
+        match other {
+            Some("json") => OutputFormat::Json,
+            _ => OutputFormat::Text,
+        }
+    }
+}
+
+enum Line {
+    Stdout(String),
+    Stderr(String),
+}
+
 pub struct Log {
     verbosity: Verbosity,
+    format: OutputFormat,
+    commands: RefCell<Vec<VcsCommand>>,
+    /// when present, output is held here until flushed rather than printed straight away
+    buffer: Option<RefCell<Vec<Line>>>,
 }
 
-impl From<u64> for Log {
-    fn from(other: u64) -> Log {
+impl Log {
+    pub fn new(verbosity: u64, format: OutputFormat) -> Log {
         Log {
-            verbosity: match other {
+            verbosity: match verbosity {
                 0 => Verbosity::None,
                 1 => Verbosity::Info,
                 2 => Verbosity::Verbose,
                 _ => Verbosity::Verbose,
             },
+            format,
+            commands: RefCell::new(Vec::new()),
+            buffer: None,
+        }
+    }
+
+    /// creates a log that holds on to its output until passed to `flush`, so that the output of
+    /// work done in parallel can be kept together
+    pub fn buffered(&self) -> Log {
+        Log {
+            verbosity: self.verbosity,
+            format: self.format,
+            commands: RefCell::new(Vec::new()),
+            buffer: Some(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// outputs everything held by a buffered log
+    pub fn flush(&self, other: Log) {
+        if let Some(buffer) = other.buffer {
+            for line in buffer.into_inner() {
+                self.print(line);
+            }
+        }
+    }
+
+    fn print(&self, line: Line) {
+        match self.buffer {
+            Some(ref buffer) => buffer.borrow_mut().push(line),
+            None => match line {
+                Line::Stdout(text) => println!("{}", text),
+                Line::Stderr(text) => eprintln!("{}", text),
+            },
         }
     }
-}
 
-impl Log {
     pub fn log(&self, verbosity: Verbosity, text: String) {
         if self.verbosity as usize >= verbosity as usize {
-            println!("{}", text);
+            // keep stdout clean for tools reading json events
+            self.print(match self.format {
+                OutputFormat::Text => Line::Stdout(text),
+                OutputFormat::Json => Line::Stderr(text),
+            });
         }
     }
 
@@ -37,5 +100,23 @@ impl Log {
     pub fn info(&self, text: String) {
         self.log(Verbosity::Info, text)
     }
-}
 
+    pub fn event(&self, event: Event) {
+        self.print(Line::Stdout(match self.format {
+            OutputFormat::Text => event.to_string(),
+            OutputFormat::Json => serde_json::to_string(&event)
+                .expect("events are always representable as json"),
+        }));
+    }
+
+    /// notes that a VCS command is being run, for reporting alongside its outcome
+    pub fn command(&self, cmd: &VcsCommand) {
+        self.info(format!("running command: {:?}", cmd));
+        self.commands.borrow_mut().push(cmd.clone());
+    }
+
+    /// returns the commands run since this was last called
+    pub fn take_commands(&self) -> Vec<VcsCommand> {
+        self.commands.replace(Vec::new())
+    }
+}
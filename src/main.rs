@@ -4,6 +4,12 @@ extern crate failure;
 extern crate clap;
 extern crate subprocess;
 extern crate strfmt;
+extern crate sha2;
+extern crate tar;
+extern crate flate2;
+extern crate xz2;
+extern crate zip;
+extern crate semver;
 
 #[macro_use]
 extern crate serde_derive;
@@ -14,15 +20,27 @@ mod dependency;
 mod path_utils;
 mod arg_utils;
 mod derpyfile;
+mod lockfile;
+mod overrides;
 mod acquire;
+mod hooks;
+mod patches;
+mod resolve;
 mod consts;
 mod error;
 mod cmds;
 mod vcs;
+mod archive;
+mod path_dependency;
+mod vendor;
+mod versions;
 mod log;
+mod events;
 
-use arg_utils::validate_option_key_value;
+use arg_utils::{validate_job_count, validate_option_key_value};
+use log::{Log, OutputFormat};
 use error::DerpyError;
+use events::Event;
 
 fn main() {
     use clap::{Arg, SubCommand};
@@ -35,12 +53,25 @@ fn main() {
             .short("p")
             .long("path")
             .help("path to treat as current working directory")
-            .takes_value(true))
+            .takes_value(true)
+            .global(true))
+        .arg(Arg::with_name("format")
+            .long("format")
+            .help("how to print output, json prints one event per line")
+            .takes_value(true)
+            .possible_values(&["text", "json"])
+            .default_value("text")
+            .global(true))
+        .arg(Arg::with_name("offline")
+            .long("offline")
+            .help("never uses the network, failing if any dependency isn't available locally")
+            .global(true))
         .arg(Arg::with_name("verbosity")
             .short("v")
             .long("verbose")
             .help("increases verbosity of output")
-            .multiple(true))
+            .multiple(true)
+            .global(true))
         .subcommand(SubCommand::with_name("init")
             .about("initialises derpy in the current directory"))
         .subcommand(SubCommand::with_name("add")
@@ -60,7 +91,18 @@ fn main() {
             .arg(Arg::with_name("version")
                 .long("version")
                 .takes_value(true)
-                .help("the version of the dependency to fetch (branch, commit, revision, etc.)"))
+                .help("the version of the dependency to fetch (branch, commit, revision, etc. or a range of tags such as ^1.4)"))
+            .arg(Arg::with_name("target")
+                .long("target")
+                .takes_value(true)
+                .help("the directory to place the dependency's folder in, relative to the project (defaults to deps/)"))
+            .arg(Arg::with_name("subdir")
+                .long("subdir")
+                .takes_value(true)
+                .help("places only this directory within the repository in the project"))
+            .arg(Arg::with_name("allow_external")
+                .long("allow-external")
+                .help("allows --target to place the dependency outside of the project directory"))
             .arg(Arg::with_name("options")
                 .long("option")
                 .takes_value(true)
@@ -68,7 +110,13 @@ fn main() {
                 .validator(validate_option_key_value)
                 .help("specifies KEY:VALUE options to associate with the dependency")))
         .subcommand(SubCommand::with_name("acquire")
-            .about("ensures all required dependencies are fetched to the current (locked) version"))
+            .about("ensures all required dependencies are fetched to the current (locked) version")
+            .arg(jobs_arg())
+            .arg(Arg::with_name("vendored")
+                .long("vendored")
+                .takes_value(true)
+                .help("restores dependencies from a directory made by vendor, without using their version control systems"))
+            .arg(dry_run_arg().conflicts_with("vendored")))
         .subcommand(SubCommand::with_name("upgrade")
             .about("like acquire but ignores the lockfile, allowing dependencies to update")
             .group(clap::ArgGroup::with_name("deps")
@@ -79,25 +127,90 @@ fn main() {
                 .help("indicates that all dependencies should be upgraded"))
             .arg(Arg::with_name("dependencies")
                 .multiple(true)
-                .help("specifies dependencies to upgrade")))
+                .help("specifies dependencies to upgrade"))
+            .arg(Arg::with_name("breaking")
+                .long("breaking")
+                .help("allows dependencies with version ranges to upgrade to the newest tag, even outside their range"))
+            .arg(dry_run_arg())
+            .arg(jobs_arg()))
+        .subcommand(SubCommand::with_name("remove")
+            .about("removes a dependency from the current project")
+            .arg(Arg::with_name("name")
+                .takes_value(true)
+                .required(true)
+                .help("the name of the dependency to remove"))
+            .arg(Arg::with_name("delete")
+                .long("delete")
+                .help("also deletes the dependency's files, refusing if they have local modifications"))
+            .arg(Arg::with_name("force")
+                .long("force")
+                .requires("delete")
+                .help("deletes the dependency's files without checking for local modifications")))
+        .subcommand(SubCommand::with_name("status")
+            .about("reports how dependencies on disk compare to the config and lock files, without changing anything"))
+        .subcommand(SubCommand::with_name("outdated")
+            .about("reports how locked dependencies compare to the newest versions upstream, without changing anything")
+            .arg(jobs_arg()))
+        .subcommand(SubCommand::with_name("vendor")
+            .about("exports every locked dependency without version control metadata, e.g. for a source release")
+            .arg(Arg::with_name("dir")
+                .takes_value(true)
+                .required(true)
+                .help("the directory to export dependencies into")))
+        .subcommand(SubCommand::with_name("vcs")
+            .about("inspects the version control systems derpy knows about")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("list")
+                .about("lists each known version control system and the file defining it"))
+            .subcommand(SubCommand::with_name("dump")
+                .about("prints a built in version control system definition, to copy and customise")
+                .arg(Arg::with_name("name")
+                    .takes_value(true)
+                    .required(true)
+                    .help("the name of the version control system"))))
         .get_matches();
 
-    match run_cli(matches) {
-        Err(e) => println!("error: {}", e),
-        _ => {},
+    let log = Log::new(0, OutputFormat::from(matches.value_of("format")));
+    if let Err(e) = run_cli(matches) {
+        log.event(Event::Error { error: e });
+        std::process::exit(1);
     }
 }
 
+fn jobs_arg<'a, 'b>() -> clap::Arg<'a, 'b> {
+    clap::Arg::with_name("jobs")
+        .short("j")
+        .long("jobs")
+        .takes_value(true)
+        .validator(validate_job_count)
+        .help("the number of dependencies to fetch at the same time")
+}
+
+fn dry_run_arg<'a, 'b>() -> clap::Arg<'a, 'b> {
+    clap::Arg::with_name("dry_run")
+        .long("dry-run")
+        .help("reports what would be done, including the commands that would be run, without changing anything")
+}
+
 fn run_cli(matches: clap::ArgMatches) -> Result<(), DerpyError> {
     match matches.subcommand() {
         ("init", Some(matches)) => cmds::cli_init(cmds::CommandContext::from_args(matches.clone())?),
         ("add", Some(matches)) => cmds::cli_add(cmds::CommandContext::from_args(matches.clone())?),
         ("acquire", Some(matches)) => cmds::cli_acquire(cmds::CommandContext::from_args(matches.clone())?),
         ("upgrade", Some(matches)) => cmds::cli_upgrade(cmds::CommandContext::from_args(matches.clone())?),
+        ("remove", Some(matches)) => cmds::cli_remove(cmds::CommandContext::from_args(matches.clone())?),
+        ("status", Some(matches)) => cmds::cli_status(cmds::CommandContext::from_args(matches.clone())?),
+        ("outdated", Some(matches)) => cmds::cli_outdated(cmds::CommandContext::from_args(matches.clone())?),
+        ("vendor", Some(matches)) => cmds::cli_vendor(cmds::CommandContext::from_args(matches.clone())?),
+        ("vcs", Some(matches)) => match matches.subcommand() {
+            ("list", Some(matches)) => cmds::cli_vcs_list(cmds::CommandContext::from_args(matches.clone())?),
+            ("dump", Some(matches)) => cmds::cli_vcs_dump(cmds::CommandContext::from_args(matches.clone())?),
+            _ => unreachable!(),
+        },
         ("", None) => {
             Err(DerpyError::InvalidArguments {
                 reason: "no subcommand was used".into(),
-            }.into())
+            })
         },
         _ => unreachable!(),
     }
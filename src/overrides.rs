@@ -0,0 +1,95 @@
+use std::fs::File;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+use serde_json;
+
+use dependency::Dependency;
+use error::DerpyError;
+
+/// Replaces where a dependency comes from, for this checkout of the project only. Either `path`
+/// is given, to use a local directory as a path dependency, or any of the others are.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Override {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcs: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// The contents of `derpy.override.json`, which isn't meant to be committed, letting developers
+/// try out changes to dependencies without editing the shared config or lock file.
+#[derive(Serialize, Deserialize, Default)]
+pub struct OverrideFile {
+    #[serde(default)]
+    pub overrides: BTreeMap<String, Override>,
+}
+
+impl OverrideFile {
+    pub fn is_overridden(&self, name: &str) -> bool {
+        self.overrides.contains_key(name)
+    }
+
+    /// the dependency to use in place of the one given, which is unchanged if it isn't overridden
+    pub fn apply(&self, dependency: Dependency) -> Result<Dependency, DerpyError> {
+        let over = match self.overrides.get(&dependency.name) {
+            Some(over) => over,
+            None => return Ok(dependency),
+        };
+
+        if let Some(ref path) = over.path {
+            if over.vcs.is_some() || over.url.is_some() || over.version.is_some() {
+                return Err(DerpyError::InvalidOverride {
+                    name: dependency.name,
+                    reason: "a path can't be combined with a vcs, url or version".into(),
+                });
+            }
+            return Ok(Dependency {
+                vcs: "path".into(),
+                url: path.clone(),
+                version: "latest".into(),
+                options: BTreeMap::new(),
+                // the local directory is used as it is, which may well already have the fixes
+                patches: Vec::new(),
+                ..dependency
+            });
+        }
+
+        Ok(Dependency {
+            vcs: over.vcs.clone().unwrap_or(dependency.vcs),
+            url: over.url.clone().unwrap_or(dependency.url),
+            version: over.version.clone().unwrap_or(dependency.version),
+            ..dependency
+        })
+    }
+}
+
+/// loads the override file, which is treated as empty if it doesn't exist
+pub fn load_overrides<P: AsRef<Path>>(path: P) -> Result<OverrideFile, DerpyError> {
+    if !path.as_ref().is_file() {
+        return Ok(OverrideFile::default());
+    }
+
+    let mut contents = String::new();
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => return Err(DerpyError::UnableToOpenOverrideFile {
+            error: e,
+        }),
+    };
+    if let Err(e) = file.read_to_string(&mut contents) {
+        return Err(DerpyError::UnableToReadOverrideFile {
+            error: e,
+        });
+    }
+    match serde_json::from_str(&contents) {
+        Ok(overrides) => Ok(overrides),
+        Err(e) => Err(DerpyError::UnableToDecodeOverrideFile {
+            error: e,
+        })
+    }
+}
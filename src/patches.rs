@@ -0,0 +1,204 @@
+use std::fs::{read, write, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use serde_json;
+use consts::PATCH_STATE_DIR;
+use dependency::Dependency;
+use path_utils::{ensure_dir, remove_dir};
+use error::DerpyError;
+use vcs::{run_cmd, Vcs, VcsCommand};
+use log::Log;
+
+/// A patch applied on top of a dependency, as recorded in the lock file.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct LockedPatch {
+    /// where the patch is, relative to the project
+    pub path: String,
+    pub sha256: String,
+}
+
+/// The patches currently applied to a dependency's checkout, kept in `.derpy/patches/<name>/`
+/// along with a copy of each patch, so they can be reverted even once the originals have changed.
+#[derive(Serialize, Deserialize, Default)]
+struct AppliedPatches {
+    patches: Vec<LockedPatch>,
+}
+
+const APPLIED_PATCHES_FILE: &str = "applied.json";
+
+fn state_dir(root: &Path, dep: &Dependency) -> PathBuf {
+    root.join(PATCH_STATE_DIR).join(&dep.name)
+}
+
+/// the copy kept of the patch applied at `index`, for reverting it later
+fn patch_copy(root: &Path, dep: &Dependency, index: usize) -> PathBuf {
+    state_dir(root, dep).join(format!("{:04}.patch", index))
+}
+
+fn read_patch(root: &Path, dep: &Dependency, path: &str) -> Result<Vec<u8>, DerpyError> {
+    match read(root.join(path)) {
+        Ok(contents) => Ok(contents),
+        Err(e) => Err(DerpyError::UnableToReadPatch {
+            name: dep.name.clone(),
+            patch: path.into(),
+            error: e,
+        }),
+    }
+}
+
+/// the dependency's patches, with the hash of each as it is now
+pub fn wanted_patches(root: &Path, dep: &Dependency) -> Result<Vec<LockedPatch>, DerpyError> {
+    let mut patches = Vec::new();
+    for path in dep.patches.iter() {
+        let contents = read_patch(root, dep, path)?;
+        patches.push(LockedPatch {
+            path: path.clone(),
+            sha256: format!("{:x}", Sha256::digest(&contents)),
+        });
+    }
+    Ok(patches)
+}
+
+fn load_applied(root: &Path, dep: &Dependency) -> Result<AppliedPatches, DerpyError> {
+    let path = state_dir(root, dep).join(APPLIED_PATCHES_FILE);
+    if !path.is_file() {
+        return Ok(AppliedPatches::default());
+    }
+    let mut contents = String::new();
+    if let Err(e) = File::open(&path).and_then(|mut file| file.read_to_string(&mut contents)) {
+        return Err(DerpyError::UnableToReadPatchState {
+            path,
+            error: e,
+        });
+    }
+    match serde_json::from_str(&contents) {
+        Ok(applied) => Ok(applied),
+        Err(e) => Err(DerpyError::UnableToDecodePatchState {
+            path,
+            error: e,
+        }),
+    }
+}
+
+fn save_applied(root: &Path, dep: &Dependency, applied: &AppliedPatches) -> Result<(), DerpyError> {
+    let path = state_dir(root, dep).join(APPLIED_PATCHES_FILE);
+    let contents = serde_json::to_string_pretty(applied)
+        .expect("applied patches are always representable as json");
+    if let Err(e) = write(&path, contents) {
+        return Err(DerpyError::UnableToWritePatchState {
+            path,
+            error: e,
+        });
+    }
+    Ok(())
+}
+
+fn patch_cmd(patch: &Path, reverse: bool, dry_run: bool) -> VcsCommand {
+    let mut cmd = vec!["patch".to_string(), "-p1".into(), "--batch".into(), "--forward".into()];
+    if reverse {
+        cmd.push("--reverse".into());
+    }
+    if dry_run {
+        cmd.push("--dry-run".into());
+    }
+    cmd.push("--input".into());
+    cmd.push(patch.to_string_lossy().into_owned());
+    cmd
+}
+
+/// Runs `patch`, first checking that the whole patch applies so a patch that no longer fits
+/// doesn't leave the checkout half patched. Returns the output of `patch` if it doesn't apply.
+fn run_patch(log: &Log, dir: &Path, patch: &Path, reverse: bool) -> Result<Option<String>, DerpyError> {
+    for &dry_run in [true, false].iter() {
+        match run_cmd(log, &patch_cmd(patch, reverse, dry_run), dir) {
+            Ok(_) => {},
+            Err(DerpyError::VcsCommandFailed { stdout, stderr, .. }) => {
+                return Ok(Some(format!("{}{}", stdout, stderr).trim().into()));
+            },
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// Whether the dependency has local modifications, as `Vcs::is_dirty`, except that there is no
+/// way to tell local modifications apart from those made by its patches once they are applied.
+pub fn is_modified(vcs: &dyn Vcs, log: &Log, root: &Path, dep: &Dependency) -> Result<Option<bool>, DerpyError> {
+    if state_dir(root, dep).is_dir() {
+        return Ok(None);
+    }
+    vcs.is_dirty(log, root, dep)
+}
+
+/// forgets about the patches applied to a dependency that has been deleted
+pub fn forget_patches(root: &Path, dep: &Dependency) -> Result<(), DerpyError> {
+    let state_dir = state_dir(root, dep);
+    if state_dir.is_dir() {
+        remove_dir(&state_dir)?;
+    }
+    Ok(())
+}
+
+/// Reverts any patches applied to the dependency's checkout in `dir`, most recent first, ready
+/// for its VCS to change it. Forgets about them without reverting them if `dir` has gone.
+pub fn revert_patches(log: &Log, root: &Path, dep: &Dependency, dir: &Path) -> Result<bool, DerpyError> {
+    let state_dir = state_dir(root, dep);
+    if !state_dir.is_dir() {
+        return Ok(false);
+    }
+    let applied = load_applied(root, dep)?;
+    if dir.is_dir() {
+        for (index, patch) in applied.patches.iter().enumerate().rev() {
+            log.info(format!("reverting patch {} of '{}'", patch.path, dep.name));
+            if let Some(output) = run_patch(log, dir, &patch_copy(root, dep, index), true)? {
+                return Err(DerpyError::UnableToRevertPatch {
+                    name: dep.name.clone(),
+                    patch: patch.path.clone(),
+                    output,
+                });
+            }
+        }
+    }
+    remove_dir(&state_dir)?;
+    Ok(!applied.patches.is_empty())
+}
+
+/// Makes sure exactly the dependency's current patches are applied to its checkout in `dir`,
+/// reverting and reapplying them all if they have changed since they were applied. Returns
+/// whether the checkout was changed.
+pub fn update_patches(log: &Log, root: &Path, dep: &Dependency, dir: &Path) -> Result<bool, DerpyError> {
+    let wanted = wanted_patches(root, dep)?;
+    if load_applied(root, dep)?.patches == wanted {
+        return Ok(false);
+    }
+    revert_patches(log, root, dep, dir)?;
+    if wanted.is_empty() {
+        return Ok(true);
+    }
+
+    ensure_dir(state_dir(root, dep))?;
+    let mut applied = AppliedPatches::default();
+    for (index, patch) in wanted.into_iter().enumerate() {
+        let copy = patch_copy(root, dep, index);
+        if let Err(e) = write(&copy, read_patch(root, dep, &patch.path)?) {
+            return Err(DerpyError::UnableToWritePatchState {
+                path: copy,
+                error: e,
+            });
+        }
+        log.info(format!("applying patch {} to '{}'", patch.path, dep.name));
+        if let Some(output) = run_patch(log, dir, &copy, false)? {
+            // record what did apply, so that it can be reverted next time
+            save_applied(root, dep, &applied)?;
+            return Err(DerpyError::PatchDoesNotApply {
+                name: dep.name.clone(),
+                patch: patch.path,
+                output,
+            });
+        }
+        applied.patches.push(patch);
+    }
+    save_applied(root, dep, &applied)?;
+    Ok(true)
+}
@@ -0,0 +1,198 @@
+use std::fs::{read_dir, symlink_metadata, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use dependency::Dependency;
+use acquire::load_source;
+use path_utils::{copy_dir, normalise_path, remove_dir, remove_symlink};
+use error::DerpyError;
+use vcs::{export_dir, run_cmd, Vcs, VCS_METADATA};
+use log::Log;
+
+enum SyncMethod {
+    Symlink,
+    Copy,
+}
+
+/// Mirrors a directory elsewhere on disk into the project, for developing a dependency alongside
+/// the project using it. The directory is symlinked into place where the platform allows,
+/// otherwise (or when the dependency's `method` option is `copy`) it is copied. Its version is
+/// its git revision if it is a git repository, otherwise a hash of its contents.
+pub struct PathVcs;
+
+impl PathVcs {
+    /// the directory being mirrored, relative to the project unless absolute
+    fn source_dir(root: &Path, dependency: &Dependency) -> PathBuf {
+        let url = &dependency.url;
+        normalise_path(&root.join(url.strip_prefix("file://").unwrap_or(url)))
+    }
+
+    fn sync_method(dependency: &Dependency) -> Result<SyncMethod, DerpyError> {
+        match dependency.options.get("method").map(|method| method.as_str()) {
+            None if cfg!(any(unix, windows)) => Ok(SyncMethod::Symlink),
+            None => Ok(SyncMethod::Copy),
+            Some("symlink") => Ok(SyncMethod::Symlink),
+            Some("copy") => Ok(SyncMethod::Copy),
+            Some(other) => Err(DerpyError::InvalidDependencyOption {
+                name: dependency.name.clone(),
+                option: "method".into(),
+                value: other.into(),
+            }),
+        }
+    }
+
+    /// (re)creates the dependency's directory from the source directory
+    fn sync(log: &Log, root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        let source = Self::source_dir(root, dependency);
+        if !source.is_dir() {
+            return Err(DerpyError::MissingPathDependency {
+                name: dependency.name.clone(),
+                path: source,
+            });
+        }
+        let method = Self::sync_method(dependency)?;
+
+        let dest = dependency.get_checkout_dir_in(root);
+        if let Ok(metadata) = symlink_metadata(&dest) {
+            if metadata.file_type().is_symlink() {
+                remove_symlink(&dest)?;
+            } else {
+                // only a copy made by derpy is replaced, anything else may be someone's work
+                let is_copy = match load_source(root, dependency)? {
+                    Some(source) => source.vcs == "path",
+                    None => false,
+                };
+                match method {
+                    SyncMethod::Copy if is_copy => remove_dir(&dest)?,
+                    _ => return Err(DerpyError::DependencyInTheWay {
+                        name: dependency.name.clone(),
+                        path: dest,
+                    }),
+                }
+            }
+        }
+
+        match method {
+            SyncMethod::Symlink => {
+                log.info(format!("linking {:?} to {:?}", dest, source));
+                if let Err(e) = symlink_dir(&source, &dest) {
+                    return Err(DerpyError::UnableToLinkDirectory {
+                        path: dest,
+                        error: e,
+                    });
+                }
+            },
+            SyncMethod::Copy => {
+                log.info(format!("copying {:?} to {:?}", source, dest));
+                if let Err(e) = copy_dir(&source, &dest, &[]) {
+                    return Err(DerpyError::UnableToCopyDirectory {
+                        path: source,
+                        error: e,
+                    });
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+impl Vcs for PathVcs {
+    fn get_name(&self) -> &str { "path" }
+
+    fn get_version(&self, _log: &Log) -> Result<String, DerpyError> {
+        Ok(format!("derpy {}", env!("CARGO_PKG_VERSION")))
+    }
+
+    fn get_default_version(&self) -> &str { "latest" }
+
+    fn acquire(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        Self::sync(log, root, dependency)
+    }
+
+    /// A directory can't be taken back to an earlier version, so this only succeeds if it is
+    /// already at the version wanted, otherwise the dependency needs upgrading to its new version.
+    fn checkout(&self, log: &Log, root: &Path, dependency: &Dependency, at_version: &str) -> Result<(), DerpyError> {
+        Self::sync(log, root, dependency)?;
+        let version = self.get_version_of(log, root, dependency)?;
+        if version != at_version {
+            return Err(DerpyError::PathDependencyChanged {
+                name: dependency.name.clone(),
+                expected: at_version.into(),
+                actual: version,
+            });
+        }
+        Ok(())
+    }
+
+    fn upgrade(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        Self::sync(log, root, dependency)
+    }
+
+    fn get_version_of(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<String, DerpyError> {
+        let dir = dependency.get_checkout_dir_in(root);
+        if dir.join(".git").exists() {
+            let cmd = vec!["git".into(), "rev-parse".into(), "HEAD".into()];
+            let (stdout, _) = run_cmd(log, &cmd, &dir)?;
+            return Ok(stdout.trim().into());
+        }
+
+        let mut hasher = Sha256::new();
+        if let Err(e) = hash_dir(&mut hasher, &dir, Path::new("")) {
+            return Err(DerpyError::UnableToHashDirectory {
+                path: dir,
+                error: e,
+            });
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// a symlinked dependency is never modified, since any changes are made to the original
+    fn is_dirty(&self, _log: &Log, root: &Path, dependency: &Dependency) -> Result<Option<bool>, DerpyError> {
+        match symlink_metadata(dependency.get_full_path_in(root)) {
+            Ok(ref metadata) if metadata.file_type().is_symlink() => Ok(Some(false)),
+            _ => Ok(None),
+        }
+    }
+
+    /// the directory isn't managed by derpy's VCS, so any metadata in it is left out as usual
+    fn export(&self, log: &Log, root: &Path, dependency: &Dependency, dest: &Path) -> Result<(), DerpyError> {
+        export_dir(log, &dependency.get_full_path_in(root), dest, VCS_METADATA)
+    }
+}
+
+/// hashes the path and contents of every file in a directory, in a consistent order
+fn hash_dir(hasher: &mut Sha256, dir: &Path, relative: &Path) -> io::Result<()> {
+    let mut entries = read_dir(dir)?
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let relative = relative.join(entry.file_name());
+        if path.is_dir() {
+            hash_dir(hasher, &path, &relative)?;
+        } else {
+            let mut contents = Vec::new();
+            File::open(&path)?.read_to_end(&mut contents)?;
+            hasher.update(relative.to_string_lossy().replace('\\', "/").as_bytes());
+            hasher.update([0u8]);
+            hasher.update(Sha256::digest(&contents));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink_dir(source: &Path, dest: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(source, dest)
+}
+
+#[cfg(windows)]
+fn symlink_dir(source: &Path, dest: &Path) -> io::Result<()> {
+    ::std::os::windows::fs::symlink_dir(source, dest)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink_dir(_source: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "symlinks are not supported on this platform"))
+}
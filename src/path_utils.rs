@@ -1,26 +1,43 @@
-use std::env::{current_dir, current_exe};
-use std::path::{Path, PathBuf};
-use std::fs::create_dir_all;
+use std::env::{current_dir, current_exe, var_os};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::fs::{self, copy, create_dir_all, read_dir, read_link, remove_dir_all, remove_file};
+use consts::CACHE_DIR_VAR;
 use error::DerpyError;
 
+/// The directory derpy was installed to. Debug builds are no different, as the definitions
+/// shipped alongside the code are built into them anyway.
 pub fn install_dir() -> Result<PathBuf, DerpyError> {
-    let dir = if cfg!(debug_assertions) {
-        match current_dir() {
-            Ok(dir) => dir,
-            Err(e) => return Err(DerpyError::UnableToDetermineCurrentDir {
-                error: e,
-            }),
-        }
-    } else {
-        match current_exe() {
-            Ok(dir) => dir,
-            Err(e) => return Err(DerpyError::UnableToDetermineCurrentExePath {
-                error: e,
-            })
-        }
+    let exe = match current_exe() {
+        Ok(exe) => exe,
+        Err(e) => return Err(DerpyError::UnableToDetermineCurrentExePath {
+            error: e,
+        })
     };
 
-    Ok(dir)
+    match exe.parent() {
+        Some(dir) => Ok(dir.to_path_buf()),
+        None => Err(DerpyError::UnableToDetermineCurrentExePath {
+            error: io::Error::new(io::ErrorKind::NotFound, "executable path has no parent directory"),
+        }),
+    }
+}
+
+/// The per-user configuration directory, following the XDG base directory spec.
+pub fn user_config_dir() -> Option<PathBuf> {
+    match var_os("XDG_CONFIG_HOME") {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    }
+}
+
+/// Where derpy keeps mirrors of repositories shared between projects, `None` unless `DERPY_CACHE`
+/// is set to a directory for them, since the cache is opt-in.
+pub fn cache_dir() -> Option<PathBuf> {
+    match var_os(CACHE_DIR_VAR) {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => None,
+    }
 }
 
 pub fn ensure_dir<P: AsRef<Path>>(path: P) -> Result<(), DerpyError> {
@@ -32,6 +49,96 @@ pub fn ensure_dir<P: AsRef<Path>>(path: P) -> Result<(), DerpyError> {
     Ok(())
 }
 
+/// Normalises a path without touching the filesystem, removing `.` components and resolving
+/// `..` components against whatever precedes them.
+pub fn normalise_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => match result.components().next_back() {
+                Some(Component::Normal(_)) => { result.pop(); },
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {},
+                _ => result.push(".."),
+            },
+            other => result.push(other.as_os_str()),
+        }
+    }
+    result
+}
+
+/// whether a path is relative and stays within the directory it is relative to
+pub fn is_contained_path(path: &Path) -> bool {
+    let normalised = normalise_path(path);
+    normalised.is_relative() && normalised.components().all(|component| component != Component::ParentDir)
+}
+
+pub fn remove_dir<P: AsRef<Path>>(path: P) -> Result<(), DerpyError> {
+    if let Err(e) = remove_dir_all(path) {
+        return Err(DerpyError::FailedToRemoveDirectory {
+            error: e,
+        })
+    }
+    Ok(())
+}
+
+/// Copies a directory and everything in it, leaving out any files or directories named in
+/// `exclude`. Symlinks within it are copied as symlinks, rather than what they point to.
+pub fn copy_dir(source: &Path, dest: &Path, exclude: &[&str]) -> io::Result<()> {
+    create_dir_all(dest)?;
+    for entry in read_dir(source)? {
+        let entry = entry?;
+        if exclude.iter().any(|name| entry.file_name() == *name) {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            copy_symlink(&path, &dest.join(entry.file_name()))?;
+        } else if file_type.is_dir() {
+            copy_dir(&path, &dest.join(entry.file_name()), exclude)?;
+        } else {
+            copy(&path, dest.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(link: &Path, dest: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(read_link(link)?, dest)
+}
+
+#[cfg(windows)]
+fn copy_symlink(link: &Path, dest: &Path) -> io::Result<()> {
+    if link.is_dir() {
+        ::std::os::windows::fs::symlink_dir(read_link(link)?, dest)
+    } else {
+        ::std::os::windows::fs::symlink_file(read_link(link)?, dest)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn copy_symlink(link: &Path, dest: &Path) -> io::Result<()> {
+    read_link(link)?;
+    Err(io::Error::new(io::ErrorKind::Other, "symlinks are not supported on this platform"))
+}
+
+/// removes a symlink to a directory, leaving the directory it points to alone
+pub fn remove_symlink<P: AsRef<Path>>(path: P) -> Result<(), DerpyError> {
+    let result = if cfg!(windows) {
+        fs::remove_dir(path)
+    } else {
+        remove_file(path)
+    };
+    if let Err(e) = result {
+        return Err(DerpyError::FailedToRemoveDirectory {
+            error: e,
+        })
+    }
+    Ok(())
+}
+
 pub fn determine_cwd(override_path: Option<&str>) -> Result<PathBuf, DerpyError> {
     let path = match override_path {
         Some(path) => {
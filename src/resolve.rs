@@ -0,0 +1,229 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::fmt;
+use derpyfile::{DerpyFile, load_config};
+use overrides::OverrideFile;
+use dependency::Dependency;
+use consts::CONFIG_FILE;
+use error::DerpyError;
+use events::Event;
+use log::Log;
+
+/// who asked for a dependency, `None` being the project derpy was invoked on
+pub type Requester = Option<String>;
+
+pub struct ResolvedDependency {
+    pub dependency: Dependency,
+    pub required_by: Vec<Requester>,
+}
+
+pub type ResolvedDependencies = BTreeMap<String, ResolvedDependency>;
+
+/// one side of a clash between two requirements for the same dependency
+#[derive(Debug, Serialize)]
+pub struct ConflictingRequirement {
+    pub requester: String,
+    pub url: String,
+    pub version: String,
+}
+
+impl fmt::Display for ConflictingRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} wants {} ({})", self.requester, self.version, self.url)
+    }
+}
+
+pub fn describe_requester(requester: &Requester) -> String {
+    match *requester {
+        Some(ref name) => format!("'{}'", name),
+        None => "the project".into(),
+    }
+}
+
+/// whether two requirements for the same dependency can be satisfied by a single checkout
+fn is_same_requirement(a: &Dependency, b: &Dependency) -> bool {
+    a.vcs == b.vcs && a.url == b.url && a.version == b.version && a.subdir == b.subdir
+}
+
+/// finds a chain of requirements leading from `ancestor` down to `descendant`, if one exists
+fn find_requirement_chain(resolved: &ResolvedDependencies, ancestor: &str, descendant: &str) -> Option<Vec<String>> {
+    if ancestor == descendant {
+        return Some(vec![descendant.into()]);
+    }
+
+    let requesters = &resolved.get(descendant)?.required_by;
+
+    for requester in requesters.iter() {
+        if let Some(ref requester) = *requester {
+            if let Some(mut chain) = find_requirement_chain(resolved, ancestor, requester) {
+                chain.push(descendant.into());
+                return Some(chain);
+            }
+        }
+    }
+
+    None
+}
+
+/// Records that `requester` needs `dep`, returning true if the dependency had not been seen
+/// before. Requirements that clash with an earlier one are resolved in favour of the project
+/// itself, if it was the one to ask for the dependency, and are an error otherwise.
+fn add_requirement(log: &Log, resolved: &mut ResolvedDependencies, requester: Requester, dep: Dependency) -> Result<bool, DerpyError> {
+    dep.validate_name()?;
+    if let Some(ref requester_name) = requester {
+        if let Some(mut cycle) = find_requirement_chain(resolved, &dep.name, requester_name) {
+            cycle.push(dep.name.clone());
+            return Err(DerpyError::DependencyCycle { cycle });
+        }
+    }
+
+    let existing = match resolved.get_mut(&dep.name) {
+        Some(existing) => existing,
+        None => {
+            // the project's own dependencies may be placed outside of it if it says so, but those
+            // of its dependencies shouldn't be able to place files outside of it
+            if (requester.is_some() || !dep.allow_external) && !dep.is_within_project() {
+                return Err(DerpyError::DependencyOutsideProject {
+                    path: dep.get_full_path(),
+                    name: dep.name,
+                });
+            }
+            if let Some(other) = resolved.values().find(|other| other.dependency.shares_path_with(&dep)) {
+                return Err(DerpyError::DependencyPathClash {
+                    path: dep.get_full_path(),
+                    name: dep.name,
+                    other_name: other.dependency.name.clone(),
+                });
+            }
+            resolved.insert(dep.name.clone(), ResolvedDependency {
+                dependency: dep,
+                required_by: vec![requester],
+            });
+            return Ok(true);
+        },
+    };
+
+    if !is_same_requirement(&existing.dependency, &dep) {
+        if existing.required_by.contains(&None) {
+            log.event(Event::RequirementOverridden {
+                name: dep.name.clone(),
+                requester: describe_requester(&requester),
+                version: dep.version.clone(),
+                url: dep.url.clone(),
+                chosen_version: existing.dependency.version.clone(),
+                chosen_url: existing.dependency.url.clone(),
+            });
+        } else {
+            return Err(DerpyError::DependencyConflict {
+                name: dep.name,
+                first: Box::new(ConflictingRequirement {
+                    requester: describe_requester(&existing.required_by[0]),
+                    url: existing.dependency.url.clone(),
+                    version: existing.dependency.version.clone(),
+                }),
+                second: Box::new(ConflictingRequirement {
+                    requester: describe_requester(&requester),
+                    url: dep.url,
+                    version: dep.version,
+                }),
+            });
+        }
+    }
+
+    log.verbose(format!("'{}' also required by {}", dep.name, describe_requester(&requester)));
+    existing.required_by.push(requester);
+
+    Ok(false)
+}
+
+/// Visits every dependency given, running up to `jobs` visits at once. Each visit gets its own
+/// log so that its output is kept together, output is then printed in the order given.
+fn visit_all<F>(log: &Log, batch: &[Dependency], jobs: usize, visit: &F) -> Result<(), DerpyError>
+    where F: Fn(&Log, &Dependency) -> Result<(), DerpyError> + Sync
+{
+    let slots = batch.iter()
+        .map(|_| Mutex::new((log.buffered(), None)))
+        .collect::<Vec<Mutex<(Log, Option<Result<(), DerpyError>>)>>>();
+    let next = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1).min(batch.len()) {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let dep = match batch.get(index) {
+                        Some(dep) => dep,
+                        None => break,
+                    };
+                    let mut slot = slots[index].lock().unwrap();
+                    let result = visit(&slot.0, dep);
+                    slot.1 = Some(result);
+                }
+            });
+        }
+    });
+
+    let mut first_error = None;
+    for slot in slots {
+        let (dep_log, result) = slot.into_inner().unwrap();
+        log.flush(dep_log);
+        if let Some(Err(e)) = result {
+            first_error.get_or_insert(e);
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Walks the dependency graph of a project breadth first, calling `visit` for each dependency
+/// exactly once. After a dependency is visited its own config file (if any) is read and its
+/// dependencies are walked in turn. Transitive dependencies are placed relative to the project
+/// being walked, not the dependency that required them, so that they can be shared.
+///
+/// Dependencies at the same depth don't depend on each other, so up to `jobs` of them are visited
+/// in parallel. Conflicting requirements are detected as soon as they are read, before the
+/// dependency they concern is visited. Overridden dependencies are replaced wherever they are
+/// required, before any of this happens.
+pub fn walk_dependencies<F>(log: &Log, root: &Path, config: &DerpyFile, overrides: &OverrideFile, jobs: usize, visit: F) -> Result<ResolvedDependencies, DerpyError>
+    where F: Fn(&Log, &Dependency) -> Result<(), DerpyError> + Sync
+{
+    let mut resolved = ResolvedDependencies::new();
+    let mut pending = Vec::new();
+
+    for dep in config.dependencies.values() {
+        if add_requirement(log, &mut resolved, None, overrides.apply(dep.clone())?)? {
+            pending.push(dep.name.clone());
+        }
+    }
+
+    while !pending.is_empty() {
+        let batch = pending.drain(..)
+            .map(|name| resolved[&name].dependency.clone())
+            .collect::<Vec<_>>();
+
+        visit_all(log, &batch, jobs, &visit)?;
+
+        for dep in batch {
+            let sub_config_path = dep.get_full_path_in(root).join(CONFIG_FILE);
+            if sub_config_path.is_file() {
+                log.verbose(format!("reading dependencies of '{}' from {:?}", dep.name, sub_config_path));
+                let sub_config = load_config(&sub_config_path)?;
+                for sub_dep in sub_config.dependencies.into_values() {
+                    let sub_dep_name = sub_dep.name.clone();
+                    let sub_dep = sub_dep.declared_in(&dep.get_full_path());
+                    if add_requirement(log, &mut resolved, Some(dep.name.clone()), overrides.apply(sub_dep)?)? {
+                        pending.push(sub_dep_name);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(resolved)
+}
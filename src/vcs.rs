@@ -1,49 +1,31 @@
 use subprocess::{Popen, PopenConfig, Redirection};
-use std::env::{current_dir, set_current_dir};
-use std::collections::HashMap;
-use path_utils::install_dir;
+use std::collections::{BTreeMap, HashMap};
+use path_utils::{cache_dir, copy_dir, ensure_dir, install_dir, remove_dir, user_config_dir};
+use sha2::{Digest, Sha256};
+use events::Event;
 use dependency::Dependency;
-use consts::VCS_INFO_DIR;
+use consts::{PROJECT_VCS_INFO_DIR, USER_VCS_INFO_DIR, VCS_INFO_DIR, VCS_PATH_VAR};
 use error::DerpyError;
-use std::fmt::Debug;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::env::{split_paths, var_os};
 use strfmt::Format;
 use std::vec::Vec;
-use std::fs::File;
+use std::fs::{read_dir, File, OpenOptions};
 use std::io::Read;
+use std::fmt;
 use serde_json;
 use log::Log;
+use archive::ArchiveVcs;
+use path_dependency::PathVcs;
+use vendor::{is_vendored, VendoredVcs};
 
 pub type VcsCommand = Vec<String>;
 pub type VcsCommandList = Vec<VcsCommand>;
 
-fn do_in_dir<T, P: AsRef<Path> + Debug, F: FnOnce() -> Result<T, DerpyError>>(log: &Log, path: P, f: F) -> Result<T, DerpyError> {
-    let initial_dir = match current_dir() {
-        Ok(dir) => dir,
-        Err(e) => return Err(DerpyError::UnableToDetermineCurrentDir {
-            error: e,
-        }),
-    };
-    log.verbose(format!("entering dir {:?} -> {:?}", &initial_dir, &path));
-    if let Err(e) = set_current_dir(&path) {
-        return Err(DerpyError::UnableToChangeDir {
-            error: e,
-        });
-    }
-    let result = f()?;
-    log.verbose(format!("leaving dir {:?} -> {:?}", &path, &initial_dir));
-    if let Err(e) = set_current_dir(&initial_dir) {
-        return Err(DerpyError::UnableToChangeDir {
-            error: e,
-        });
-    }
-    Ok(result)
-}
-
 fn expand_vcs_command(cmd: &VcsCommand, macros: &HashMap<String, String>) -> Result<VcsCommand, DerpyError> {
     let mut result = VcsCommand::new();
     for token in cmd.iter() {
-        result.push(match token.format(&macros) {
+        result.push(match token.format(macros) {
             Ok(formatted) => formatted,
             Err(e) => return Err(DerpyError::MacroExpansionFailure {
                 source_text: token.clone(),
@@ -55,7 +37,7 @@ fn expand_vcs_command(cmd: &VcsCommand, macros: &HashMap<String, String>) -> Res
     Ok(result)
 }
 
-fn expand_vcs_command_list(list: &VcsCommandList, macros: &HashMap<String, String>) -> Result<VcsCommandList, DerpyError> {
+pub fn expand_vcs_command_list(list: &VcsCommandList, macros: &HashMap<String, String>) -> Result<VcsCommandList, DerpyError> {
     let mut result = VcsCommandList::new();
     for cmd in list.iter() {
         result.push(expand_vcs_command(cmd, macros)?);
@@ -72,117 +54,609 @@ pub struct VcsInfo {
     checkout: VcsCommandList,
     upgrade: VcsCommandList,
     get_version_of: VcsCommand,
+    /// prints any local modifications to a dependency, so no output means it is unmodified
+    #[serde(default)]
+    is_dirty: Option<VcsCommand>,
+    /// creates a mirror of a dependency's repository at `{DEP_MIRROR}` in the shared cache
+    #[serde(default)]
+    mirror: Option<VcsCommandList>,
+    /// brings an existing mirror up to date
+    #[serde(default)]
+    update_mirror: Option<VcsCommandList>,
+    /// used in place of `acquire` when there is a mirror to acquire from
+    #[serde(default)]
+    acquire_from_mirror: Option<VcsCommandList>,
+    /// copies the dependency to `{DEP_EXPORT}` without any version control metadata, if not given
+    /// the dependency's directory is copied without the usual metadata directories
+    #[serde(default)]
+    export: Option<VcsCommandList>,
+    /// narrows a checkout down to `{DEP_SUBDIR}` straight after it is acquired, for dependencies
+    /// that only want a subdirectory
+    #[serde(default)]
+    sparse_checkout: Option<VcsCommandList>,
+    /// prints the tags of `{DEP_URL}`, one per line, for resolving version ranges against. Only the
+    /// last word of each line is used, so `git ls-remote` style output is fine
+    #[serde(default)]
+    list_versions: Option<VcsCommand>,
+    /// prints the revision `{DEP_VERSION}` is at in the repository at `{DEP_URL}`, without
+    /// changing anything locally, either alone or `git ls-remote` style with the ref it is for
+    /// (see `parse_remote_version`). Printing nothing means the version isn't known upstream
+    #[serde(default)]
+    remote_version: Option<VcsCommand>,
+    /// the commands above that never use the network, and so may be run offline
+    #[serde(default)]
+    local_only: Vec<String>,
+    #[serde(skip)]
+    offline: bool,
 }
 
-impl VcsInfo {
-    pub fn get_name(&self) -> &str { &self.name }
+/// The operations derpy needs to manage a dependency, implemented either by a `VcsInfo`
+/// definition's commands or natively (see `archive` and `path_dependency`).
+pub trait Vcs {
+    fn get_name(&self) -> &str;
+
+    /// the version of the tools this relies on, failing if they aren't installed
+    fn get_version(&self, log: &Log) -> Result<String, DerpyError>;
+
+    fn get_default_version(&self) -> &str;
+
+    fn acquire(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<(), DerpyError>;
+
+    fn checkout(&self, log: &Log, root: &Path, dependency: &Dependency, at_version: &str) -> Result<(), DerpyError>;
+
+    fn upgrade(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<(), DerpyError>;
+
+    fn get_version_of(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<String, DerpyError>;
+
+    /// the commands `acquire` would run, without running them, `None` if derpy does the work itself
+    fn acquire_commands(&self, _dependency: &Dependency) -> Result<Option<VcsCommandList>, DerpyError> {
+        Ok(None)
+    }
+
+    /// the commands `checkout` would run, without running them, `None` if derpy does the work itself
+    fn checkout_commands(&self, _dependency: &Dependency, _at_version: &str) -> Result<Option<VcsCommandList>, DerpyError> {
+        Ok(None)
+    }
 
-    pub fn get_version(&self, log: &Log) -> Result<String, DerpyError> {
-        let (stdout, _) = Self::run_cmd(log, &self.get_version)?;
+    /// the commands `upgrade` would run, without running them, `None` if derpy does the work itself
+    fn upgrade_commands(&self, _dependency: &Dependency) -> Result<Option<VcsCommandList>, DerpyError> {
+        Ok(None)
+    }
+
+    /// whether the dependency has local modifications, `None` if there is no way to tell
+    fn is_dirty(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<Option<bool>, DerpyError>;
+
+    /// copies the dependency's files to `dest`, which doesn't exist yet, without any VCS metadata
+    fn export(&self, log: &Log, root: &Path, dependency: &Dependency, dest: &Path) -> Result<(), DerpyError>;
+
+    /// the tags of the dependency's repository, `None` if there is no way to list them
+    fn list_versions(&self, _log: &Log, _root: &Path, _dependency: &Dependency) -> Result<Option<Vec<String>>, DerpyError> {
+        Ok(None)
+    }
+
+    /// the revision the dependency's version is at upstream, `Ok(None)` if there is no way to ask
+    /// or the version isn't known there
+    fn remote_version(&self, _log: &Log, _root: &Path, _dependency: &Dependency) -> Result<Option<String>, DerpyError> {
+        Ok(None)
+    }
+
+    /// the directory this keeps the dependency in, which only differs from where the dependency
+    /// is placed if it wants a subdirectory (see `Dependency::get_checkout_dir_in`)
+    fn get_checkout_dir(&self, root: &Path, dependency: &Dependency) -> PathBuf {
+        dependency.get_checkout_dir_in(root)
+    }
+}
+
+impl Vcs for VcsInfo {
+    fn get_name(&self) -> &str { &self.name }
+
+    fn get_version(&self, log: &Log) -> Result<String, DerpyError> {
+        let (stdout, _) = run_cmd(log, &self.get_version, Path::new("."))?;
         Ok(stdout.trim().into())
     }
 
-    pub fn get_default_version(&self) -> &str { &self.default_version }
+    fn get_default_version(&self) -> &str { &self.default_version }
 
-    pub fn acquire(&self, log: &Log, dependency: &Dependency) -> Result<(), DerpyError> {
-        let cmd = expand_vcs_command_list(&self.acquire, &dependency.build_macro_map())?;
-        do_in_dir(log, &dependency.target, || Self::run_cmd_sequence(log, &cmd))?;
+    fn acquire(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        self.acquire_checkout(log, root, dependency)?;
+
+        if dependency.subdir.is_some() {
+            if let Some(ref sparse_checkout) = self.sparse_checkout {
+                let cmd = expand_vcs_command_list(sparse_checkout, &dependency.build_macro_map())?;
+                run_cmd_sequence(log, &cmd, &dependency.get_checkout_dir_in(root))?;
+            }
+        }
         Ok(())
     }
 
-    pub fn checkout(&self, log: &Log, dependency: &Dependency, at_version: &str) -> Result<(), DerpyError> {
+    fn checkout(&self, log: &Log, root: &Path, dependency: &Dependency, at_version: &str) -> Result<(), DerpyError> {
+        let cmd = self.checkout_commands(dependency, at_version)?.unwrap_or_default();
+        match run_cmd_sequence(log, &cmd, &dependency.get_checkout_dir_in(root)) {
+            Err(DerpyError::VcsCommandFailed { .. }) if self.offline => Err(DerpyError::UnavailableOffline {
+                name: dependency.name.clone(),
+                reason: format!("version {} isn't available locally", at_version),
+            }),
+            result => result,
+        }
+    }
+
+    fn upgrade(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        let cmd = self.upgrade_commands(dependency)?.unwrap_or_default();
+        run_cmd_sequence(log, &cmd, &dependency.get_checkout_dir_in(root))
+    }
+
+    fn get_version_of(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<String, DerpyError> {
+        let cmd = expand_vcs_command(&self.get_version_of, &dependency.build_macro_map())?;
+        let (stdout, _) = run_cmd(log, &cmd, &dependency.get_checkout_dir_in(root))?;
+        Ok(stdout.trim().into())
+    }
+
+    /// makes the same choices as `acquire`, but leaves the cached mirror as it is
+    fn acquire_commands(&self, dependency: &Dependency) -> Result<Option<VcsCommandList>, DerpyError> {
+        let mut commands = VcsCommandList::new();
+        let mirror_dir = match (&self.acquire_from_mirror, self.mirror_dir(dependency)) {
+            (Some(_), Some(mirror_dir)) if !self.offline || mirror_dir.exists() => Some(mirror_dir),
+            _ => None,
+        };
+        match mirror_dir {
+            Some(mirror_dir) => {
+                self.check_local(dependency, "acquire_from_mirror", "acquiring it from the cache may need the network")?;
+                let mut macros = dependency.build_macro_map();
+                macros.insert("DEP_MIRROR".into(), mirror_dir.to_string_lossy().into_owned());
+                let prepare = if !mirror_dir.exists() {
+                    self.mirror.as_ref()
+                } else if !self.offline {
+                    self.update_mirror.as_ref()
+                } else {
+                    None
+                };
+                if let Some(prepare) = prepare {
+                    commands.extend(expand_vcs_command_list(prepare, &macros)?);
+                }
+                if let Some(ref acquire_from_mirror) = self.acquire_from_mirror {
+                    commands.extend(expand_vcs_command_list(acquire_from_mirror, &macros)?);
+                }
+            },
+            None => {
+                self.check_local(dependency, "acquire", "it hasn't been fetched and isn't in the cache")?;
+                commands.extend(expand_vcs_command_list(&self.acquire, &dependency.build_macro_map())?);
+            },
+        }
+        if let (Some(_), Some(sparse_checkout)) = (&dependency.subdir, &self.sparse_checkout) {
+            commands.extend(expand_vcs_command_list(sparse_checkout, &dependency.build_macro_map())?);
+        }
+        Ok(Some(commands))
+    }
+
+    fn checkout_commands(&self, dependency: &Dependency, at_version: &str) -> Result<Option<VcsCommandList>, DerpyError> {
         let mut macros = dependency.build_macro_map();
         macros.insert("DEP_VERSION".into(), at_version.into());
         let cmd = expand_vcs_command_list(&self.checkout, &macros)?;
-        do_in_dir(log, dependency.get_full_path(), || Self::run_cmd_sequence(log, &cmd))?;
-        Ok(())
+        self.check_local(dependency, "checkout", "checking out another version may need the network")?;
+        Ok(Some(cmd))
     }
 
-    pub fn upgrade(&self, log: &Log, dependency: &Dependency) -> Result<(), DerpyError> {
-        let cmd = expand_vcs_command_list(&self.upgrade, &dependency.build_macro_map())?;
-        do_in_dir(log, dependency.get_full_path(), || Self::run_cmd_sequence(log, &cmd))
+    fn upgrade_commands(&self, dependency: &Dependency) -> Result<Option<VcsCommandList>, DerpyError> {
+        self.check_local(dependency, "upgrade", "upgrading it needs the network")?;
+        Ok(Some(expand_vcs_command_list(&self.upgrade, &dependency.build_macro_map())?))
     }
 
-    pub fn get_version_of(&self, log: &Log, dependency: &Dependency) -> Result<String, DerpyError> {
-        let cmd = expand_vcs_command(&self.get_version_of, &dependency.build_macro_map())?;
-        let (stdout, _) = do_in_dir(log, dependency.get_full_path(), || Self::run_cmd(log, &cmd))?;
-        Ok(stdout.trim().into())
+    /// only a copy of a subdirectory is placed in the project, and its VCS can't see changes to that
+    fn is_dirty(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<Option<bool>, DerpyError> {
+        if dependency.subdir.is_some() {
+            return Ok(None);
+        }
+        let cmd = match self.is_dirty {
+            Some(ref cmd) => expand_vcs_command(cmd, &dependency.build_macro_map())?,
+            None => return Ok(None),
+        };
+        let (stdout, _) = run_cmd(log, &cmd, &dependency.get_checkout_dir_in(root))?;
+        Ok(Some(!stdout.trim().is_empty()))
     }
 
-    fn run_cmd(log: &Log, cmd: &VcsCommand) -> Result<(String, String), DerpyError> {
-        log.info(format!("running command: {:?}", cmd));
-        let p = Popen::create(cmd, PopenConfig {
-            stdout: Redirection::Pipe,
-            stderr: Redirection::Pipe,
-            ..Default::default()
-        });
-        let mut p = match p {
-            Ok(p) => p,
-            Err(e) => return Err(DerpyError::SubprocessError {
-                cmd: cmd.clone(),
-                error: e,
-            }),
+    fn export(&self, log: &Log, root: &Path, dependency: &Dependency, dest: &Path) -> Result<(), DerpyError> {
+        let source = dependency.get_checkout_dir_in(root);
+        let export = match self.export {
+            // an export of a patched checkout would be of what the VCS has, leaving out the patches
+            Some(ref export) if dependency.subdir.is_none() && dependency.patches.is_empty() => export,
+            _ => return export_dir(log, &dependency.get_full_path_in(root), dest, VCS_METADATA),
         };
+        let mut macros = dependency.build_macro_map();
+        macros.insert("DEP_EXPORT".into(), dest.to_string_lossy().into_owned());
+        run_cmd_sequence(log, &expand_vcs_command_list(export, &macros)?, &source)
+    }
 
-        let (stdout, stderr) = match p.communicate(None) {
-            Ok(result) => result,
-            Err(e) => return Err(DerpyError::SubprocessError {
-                cmd: cmd.clone(),
-                error: e,
-            }),
+    fn list_versions(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<Option<Vec<String>>, DerpyError> {
+        let cmd = match self.list_versions {
+            Some(ref cmd) => expand_vcs_command(cmd, &dependency.build_macro_map())?,
+            None => return Ok(None),
         };
-        let (stdout, stderr) = (stdout.unwrap(), stderr.unwrap());
+        self.check_local(dependency, "list_versions", "resolving its version range needs the network")?;
+        let (stdout, _) = run_cmd(log, &cmd, root)?;
+        let tags = stdout.lines()
+            .filter_map(|line| line.split_whitespace().last())
+            .map(|tag| tag.trim_start_matches("refs/tags/").to_string())
+            .collect();
+        Ok(Some(tags))
+    }
 
-        let return_code = match p.wait() {
-            Ok(result) => result,
-            Err(e) => return Err(DerpyError::SubprocessError {
-                cmd: cmd.clone(),
-                error: e,
-            }),
+    fn remote_version(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<Option<String>, DerpyError> {
+        let cmd = match self.remote_version {
+            Some(ref cmd) => expand_vcs_command(cmd, &dependency.build_macro_map())?,
+            None => return Ok(None),
         };
+        self.check_local(dependency, "remote_version", "asking for its latest version needs the network")?;
+        let (stdout, _) = run_cmd(log, &cmd, root)?;
+        parse_remote_version(dependency, &stdout)
+    }
+}
 
-        if return_code.success() == false {
-            return Err(DerpyError::VcsCommandFailed { cmd: cmd.clone(), return_code, stdout, stderr }.into());
+/// Picks out the revision a dependency's version is at from the output of a `remote_version`
+/// command. Lines of `git ls-remote` style output give a revision and a ref, and only the ref that
+/// is exactly the version, or the branch or tag named by it, counts (an annotated tag is at the
+/// revision listed for its peeled `^{}` ref). A version naming more than one ref is an error. A
+/// line with nothing but a revision is the revision itself.
+fn parse_remote_version(dependency: &Dependency, output: &str) -> Result<Option<String>, DerpyError> {
+    let version = &dependency.version;
+    let wanted = [version.clone(), format!("refs/heads/{}", version), format!("refs/tags/{}", version)];
+    let mut matches: Vec<(&str, &str)> = Vec::new();
+    let mut plain = None;
+    for line in output.lines() {
+        let mut words = line.split_whitespace();
+        let (revision, name) = match (words.next(), words.next()) {
+            (Some(revision), Some(name)) => (revision, name),
+            (Some(revision), None) => {
+                plain = Some(revision);
+                continue;
+            },
+            _ => continue,
+        };
+        let (name, is_peeled) = match name.strip_suffix("^{}") {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        if !wanted.iter().any(|wanted| wanted == name) {
+            continue;
+        }
+        match matches.iter_mut().find(|&&mut (matched, _)| matched == name) {
+            Some(matched) if is_peeled => matched.1 = revision,
+            Some(_) => {},
+            None => matches.push((name, revision)),
         }
+    }
 
-        Ok((stdout, stderr))
+    match matches.len() {
+        0 => Ok(plain.map(|revision| revision.to_string())),
+        1 => Ok(Some(matches[0].1.to_string())),
+        _ => Err(DerpyError::AmbiguousRemoteVersion {
+            name: dependency.name.clone(),
+            version: version.clone(),
+            refs: matches.into_iter().map(|(name, _)| name.to_string()).collect(),
+        }),
     }
+}
 
-    fn run_cmd_sequence(log: &Log, sequence: &VcsCommandList) -> Result<(), DerpyError> {
-        for cmd in sequence.iter() {
-            let _output = Self::run_cmd(log, cmd)?;
+impl VcsInfo {
+    /// checks out the dependency for the first time, from the cache if possible
+    fn acquire_checkout(&self, log: &Log, root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        if let Some(ref acquire_from_mirror) = self.acquire_from_mirror {
+            // the mirror stays locked until the checkout has been cloned from it
+            if let Some((mirror_dir, _lock)) = self.prepare_mirror(log, dependency)? {
+                self.check_local(dependency, "acquire_from_mirror", "acquiring it from the cache may need the network")?;
+                let mut macros = dependency.build_macro_map();
+                macros.insert("DEP_MIRROR".into(), mirror_dir.to_string_lossy().into_owned());
+                let cmd = expand_vcs_command_list(acquire_from_mirror, &macros)?;
+                return run_cmd_sequence(log, &cmd, &dependency.get_checkout_parent_in(root));
+            }
+        }
+
+        self.check_local(dependency, "acquire", "it hasn't been fetched and isn't in the cache")?;
+        let cmd = expand_vcs_command_list(&self.acquire, &dependency.build_macro_map())?;
+        run_cmd_sequence(log, &cmd, &dependency.get_checkout_parent_in(root))
+    }
+
+    /// when offline, fails unless the named command list is known not to use the network
+    fn check_local(&self, dependency: &Dependency, commands: &str, reason: &str) -> Result<(), DerpyError> {
+        if self.offline && !self.local_only.iter().any(|name| name == commands) {
+            return Err(DerpyError::UnavailableOffline {
+                name: dependency.name.clone(),
+                reason: reason.into(),
+            });
         }
         Ok(())
     }
+
+    /// Creates the dependency's mirror in the cache, or updates it if it already exists, returning
+    /// where it is along with a lock on it, as other jobs and other projects may share the cache.
+    /// Returns `None` if this VCS can't mirror or there is no cache to keep it in.
+    /// Failing to update a mirror isn't an error, as it may well still have the version wanted.
+    fn prepare_mirror(&self, log: &Log, dependency: &Dependency) -> Result<Option<(PathBuf, File)>, DerpyError> {
+        let (mirror, mirror_dir) = match (&self.mirror, self.mirror_dir(dependency)) {
+            (Some(mirror), Some(mirror_dir)) => (mirror, mirror_dir),
+            _ => return Ok(None),
+        };
+        let cache = match mirror_dir.parent() {
+            Some(cache) => cache.to_path_buf(),
+            None => return Ok(None),
+        };
+        ensure_dir(&cache)?;
+        let lock = lock_mirror(&mirror_dir)?;
+        let mut macros = dependency.build_macro_map();
+        macros.insert("DEP_MIRROR".into(), mirror_dir.to_string_lossy().into_owned());
+
+        if !mirror_dir.exists() {
+            if self.offline {
+                return Ok(None);
+            }
+            log.info(format!("creating mirror of {} in {:?}", dependency.url, mirror_dir));
+            if let Err(error) = run_cmd_sequence(log, &expand_vcs_command_list(mirror, &macros)?, &cache) {
+                // a half made mirror would otherwise be taken for a complete one next time
+                if mirror_dir.exists() {
+                    remove_dir(&mirror_dir)?;
+                }
+                return Err(error);
+            }
+        } else if self.offline {
+            log.info(format!("using mirror {:?} without updating it", mirror_dir));
+        } else if let Some(ref update_mirror) = self.update_mirror {
+            if let Err(error) = run_cmd_sequence(log, &expand_vcs_command_list(update_mirror, &macros)?, &cache) {
+                log.event(Event::MirrorUpdateFailed {
+                    url: dependency.url.clone(),
+                    error,
+                });
+            }
+        }
+
+        Ok(Some((mirror_dir, lock)))
+    }
+
+    /// where the dependency's mirror is kept in the cache, `None` if this VCS can't mirror or
+    /// there is no cache to keep it in
+    fn mirror_dir(&self, dependency: &Dependency) -> Option<PathBuf> {
+        self.mirror.as_ref()?;
+        let cache = cache_dir()?.join(&self.name);
+
+        // mirrors are keyed by url, the last part of which is kept to make them recognisable
+        let url = &dependency.url;
+        let url_hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+        let url_tail = url.trim_end_matches('/')
+            .rsplit(['/', ':', '\\'])
+            .next()
+            .unwrap_or("")
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_' || *c == '.')
+            .collect::<String>();
+        Some(cache.join(format!("{}-{}", url_tail, &url_hash[..16])))
+    }
 }
 
-pub fn load_vcs_info(vcs_name: &str) -> Result<Option<VcsInfo>, DerpyError> {
-    let full_path = install_dir()?
-        .join(VCS_INFO_DIR)
-        .join(vcs_name)
-        .with_extension("json");
+/// waits for an exclusive lock on a mirror, held until the returned file is closed
+fn lock_mirror(mirror_dir: &Path) -> Result<File, DerpyError> {
+    let mut path = mirror_dir.as_os_str().to_owned();
+    path.push(".lock");
+    let path = PathBuf::from(path);
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)
+        .map_err(|error| DerpyError::UnableToLockMirror { path: path.clone(), error })?;
+    file.lock().map_err(|error| DerpyError::UnableToLockMirror { path, error })?;
+    Ok(file)
+}
 
-    if full_path.is_file() {
-        let mut contents = String::new();
-        let mut file = match File::open(full_path) {
-            Ok(file) => file,
-            Err(e) => return Err(DerpyError::UnableToOpenVcsInfo {
-                error: e,
-            }),
-        };
-        if let Err(e) = file.read_to_string(&mut contents) {
-            return Err(DerpyError::UnableToReadVcsInfo {
-                error: e,
-            });
+/// the files and directories version control systems keep their metadata in
+pub const VCS_METADATA: &[&str] = &[".git", ".svn", ".hg", ".fslckout", "_FOSSIL_", ".fossil-repo"];
+
+/// copies a dependency's directory to `dest`, for version control systems with no way to export
+pub fn export_dir(log: &Log, source: &Path, dest: &Path, exclude: &[&str]) -> Result<(), DerpyError> {
+    log.info(format!("copying {:?} to {:?}", source, dest));
+    if let Err(e) = copy_dir(source, dest, exclude) {
+        return Err(DerpyError::UnableToCopyDirectory {
+            path: source.into(),
+            error: e,
+        });
+    }
+    Ok(())
+}
+
+/// Runs a command as a subprocess in the directory given. Derpy's own working directory is
+/// never changed, so a failure part way through can't affect any later commands.
+pub fn run_cmd(log: &Log, cmd: &VcsCommand, dir: &Path) -> Result<(String, String), DerpyError> {
+    log.command(cmd);
+    log.verbose(format!("  (in dir {:?})", dir));
+    if !dir.is_dir() {
+        return Err(DerpyError::MissingWorkingDirectory {
+            cmd: cmd.clone(),
+            dir: dir.into(),
+        });
+    }
+    let p = Popen::create(cmd, PopenConfig {
+        stdout: Redirection::Pipe,
+        stderr: Redirection::Pipe,
+        cwd: Some(dir.as_os_str().to_owned()),
+        ..Default::default()
+    });
+    let mut p = match p {
+        Ok(p) => p,
+        Err(e) => return Err(DerpyError::SubprocessError {
+            cmd: cmd.clone(),
+            dir: dir.into(),
+            error: e,
+        }),
+    };
+
+    let (stdout, stderr) = match p.communicate(None) {
+        Ok(result) => result,
+        Err(e) => return Err(DerpyError::SubprocessError {
+            cmd: cmd.clone(),
+            dir: dir.into(),
+            error: e,
+        }),
+    };
+    let (stdout, stderr) = (stdout.unwrap(), stderr.unwrap());
+
+    let return_code = match p.wait() {
+        Ok(result) => result,
+        Err(e) => return Err(DerpyError::SubprocessError {
+            cmd: cmd.clone(),
+            dir: dir.into(),
+            error: e,
+        }),
+    };
+
+    if !return_code.success() {
+        return Err(DerpyError::VcsCommandFailed { cmd: cmd.clone(), dir: dir.into(), return_code, stdout, stderr });
+    }
+
+    Ok((stdout, stderr))
+}
+
+pub fn run_cmd_sequence(log: &Log, sequence: &VcsCommandList, dir: &Path) -> Result<(), DerpyError> {
+    for cmd in sequence.iter() {
+        let _output = run_cmd(log, cmd, dir)?;
+    }
+    Ok(())
+}
+
+/// The stock definitions compiled into derpy, used when no file on the search path defines a VCS.
+const BUILT_IN_VCS_INFO: &[(&str, &str)] = &[
+    ("git", include_str!("../vcs_info/git.json")),
+    ("svn", include_str!("../vcs_info/svn.json")),
+    ("hg", include_str!("../vcs_info/hg.json")),
+    ("p4", include_str!("../vcs_info/p4.json")),
+    ("fossil", include_str!("../vcs_info/fossil.json")),
+];
+
+/// The version control systems implemented in derpy itself rather than by a definition.
+const NATIVE_VCS: &[&str] = &["archive", "path"];
+
+fn native_vcs(vcs_name: &str) -> Option<Box<dyn Vcs>> {
+    match vcs_name {
+        "archive" => Some(Box::new(ArchiveVcs)),
+        "path" => Some(Box::new(PathVcs)),
+        _ => None,
+    }
+}
+
+pub fn built_in_vcs_info(vcs_name: &str) -> Option<&'static str> {
+    BUILT_IN_VCS_INFO.iter()
+        .find(|&&(name, _)| name == vcs_name)
+        .map(|&(_, definition)| definition)
+}
+
+/// Where a VCS definition was found.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VcsInfoSource {
+    File {
+        path: PathBuf,
+    },
+    BuiltIn,
+}
+
+impl fmt::Display for VcsInfoSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VcsInfoSource::File { ref path } => write!(f, "{:?}", path),
+            VcsInfoSource::BuiltIn => write!(f, "built in"),
         }
-        match serde_json::from_str(&contents) {
-            Ok(info) => Ok(Some(info)),
-            Err(e) => Err(DerpyError::UnableToDecodeVcsInfo {
-                error: e,
-            }),
+    }
+}
+
+/// The directories searched for VCS definitions, highest priority first: the project's own, then
+/// the user's, then any listed in `DERPY_VCS_PATH` and finally those installed with derpy.
+pub fn vcs_info_search_path(root: &Path) -> Result<Vec<PathBuf>, DerpyError> {
+    let mut search_path = vec![root.join(PROJECT_VCS_INFO_DIR)];
+    if let Some(config_dir) = user_config_dir() {
+        search_path.push(config_dir.join(USER_VCS_INFO_DIR));
+    }
+    if let Some(paths) = var_os(VCS_PATH_VAR) {
+        search_path.extend(split_paths(&paths).filter(|path| !path.as_os_str().is_empty()));
+    }
+    search_path.push(install_dir()?.join(VCS_INFO_DIR));
+    Ok(search_path)
+}
+
+/// Every VCS definition that can be found, mapped to where it is defined in search path order,
+/// so the first source for each name is the one that is used.
+pub fn find_all_vcs_info(root: &Path) -> Result<BTreeMap<String, Vec<VcsInfoSource>>, DerpyError> {
+    let mut found: BTreeMap<String, Vec<VcsInfoSource>> = BTreeMap::new();
+    for dir in vcs_info_search_path(root)? {
+        let entries = match read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        let mut paths = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                found.entry(name.to_string()).or_default().push(VcsInfoSource::File { path: path.clone() });
+            }
         }
-    } else {
-        Ok(None)
+    }
+    let built_in_names = NATIVE_VCS.iter().cloned()
+        .chain(BUILT_IN_VCS_INFO.iter().map(|&(name, _)| name));
+    for name in built_in_names {
+        found.entry(name.to_string()).or_default().push(VcsInfoSource::BuiltIn);
+    }
+    Ok(found)
+}
+
+pub fn find_vcs_info(root: &Path, vcs_name: &str) -> Result<Option<VcsInfoSource>, DerpyError> {
+    let found = vcs_info_search_path(root)?
+        .into_iter()
+        .map(|dir| dir.join(vcs_name).with_extension("json"))
+        .find(|path| path.is_file());
+    Ok(match found {
+        Some(path) => Some(VcsInfoSource::File { path }),
+        None if NATIVE_VCS.contains(&vcs_name) => Some(VcsInfoSource::BuiltIn),
+        None => built_in_vcs_info(vcs_name).map(|_| VcsInfoSource::BuiltIn),
+    })
+}
+
+/// Loads the VCS of the given name, preferring definitions on the search path over those built in.
+/// When `offline`, it refuses to run any commands that may use the network.
+pub fn load_vcs(root: &Path, vcs_name: &str, offline: bool) -> Result<Option<Box<dyn Vcs>>, DerpyError> {
+    let contents = match find_vcs_info(root, vcs_name)? {
+        Some(VcsInfoSource::File { path }) => {
+            let mut contents = String::new();
+            let mut file = match File::open(path) {
+                Ok(file) => file,
+                Err(e) => return Err(DerpyError::UnableToOpenVcsInfo {
+                    error: e,
+                }),
+            };
+            if let Err(e) = file.read_to_string(&mut contents) {
+                return Err(DerpyError::UnableToReadVcsInfo {
+                    error: e,
+                });
+            }
+            contents
+        },
+        Some(VcsInfoSource::BuiltIn) => match native_vcs(vcs_name) {
+            Some(vcs) => return Ok(Some(vcs)),
+            None => built_in_vcs_info(vcs_name).unwrap().to_string(),
+        },
+        None => return Ok(None),
+    };
+
+    match serde_json::from_str::<VcsInfo>(&contents) {
+        Ok(info) => Ok(Some(Box::new(VcsInfo { offline, ..info }))),
+        Err(e) => Err(DerpyError::UnableToDecodeVcsInfo {
+            error: e,
+        }),
     }
 }
 
+/// Loads the VCS managing a dependency, which is the one it names unless it was restored from a
+/// vendored copy, in which case there is no version control metadata for that VCS to work with.
+pub fn load_vcs_for(root: &Path, dependency: &Dependency, offline: bool) -> Result<Box<dyn Vcs>, DerpyError> {
+    if is_vendored(root, dependency) {
+        return Ok(Box::new(VendoredVcs));
+    }
+    match load_vcs(root, &dependency.vcs, offline)? {
+        Some(vcs) => Ok(vcs),
+        None => Err(DerpyError::UnknownVcs { name: dependency.vcs.clone() }),
+    }
+}
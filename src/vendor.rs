@@ -0,0 +1,160 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use lockfile::{LockFile, load_lock};
+use acquire::AcquireOutcome;
+use consts::VENDOR_MANIFEST_FILE;
+use dependency::Dependency;
+use path_utils::{copy_dir, ensure_dir, remove_dir};
+use error::DerpyError;
+use vcs::{export_dir, Vcs};
+use log::Log;
+
+/// records the revision a dependency restored from a vendored copy was vendored at
+const VENDORED_REVISION_FILE: &str = ".derpy_vendored";
+
+/// whether the dependency was restored from a vendored copy, rather than acquired with its VCS
+pub fn is_vendored(root: &Path, dependency: &Dependency) -> bool {
+    dependency.get_full_path_in(root).join(VENDORED_REVISION_FILE).is_file()
+}
+
+/// Stands in for the VCS of a dependency restored from a vendored copy, which has no version
+/// control metadata to work with. Its version is the revision it was vendored at, and it can't
+/// be changed other than by restoring it from a different vendored copy.
+pub struct VendoredVcs;
+
+impl Vcs for VendoredVcs {
+    fn get_name(&self) -> &str { "vendored" }
+
+    fn get_version(&self, _log: &Log) -> Result<String, DerpyError> {
+        Ok(format!("derpy {}", env!("CARGO_PKG_VERSION")))
+    }
+
+    fn get_default_version(&self) -> &str { "latest" }
+
+    fn acquire(&self, _log: &Log, _root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        Err(DerpyError::VendoredDependencyFixed { name: dependency.name.clone() })
+    }
+
+    fn checkout(&self, log: &Log, root: &Path, dependency: &Dependency, at_version: &str) -> Result<(), DerpyError> {
+        if self.get_version_of(log, root, dependency)? != at_version {
+            return Err(DerpyError::VendoredDependencyFixed { name: dependency.name.clone() });
+        }
+        Ok(())
+    }
+
+    fn upgrade(&self, _log: &Log, _root: &Path, dependency: &Dependency) -> Result<(), DerpyError> {
+        Err(DerpyError::VendoredDependencyFixed { name: dependency.name.clone() })
+    }
+
+    fn get_version_of(&self, _log: &Log, root: &Path, dependency: &Dependency) -> Result<String, DerpyError> {
+        let path = dependency.get_full_path_in(root).join(VENDORED_REVISION_FILE);
+        let mut revision = String::new();
+        if let Err(e) = File::open(&path).and_then(|mut file| file.read_to_string(&mut revision)) {
+            return Err(DerpyError::UnableToReadVendoredRevision {
+                path,
+                error: e,
+            });
+        }
+        Ok(revision.trim().into())
+    }
+
+    fn is_dirty(&self, _log: &Log, _root: &Path, _dependency: &Dependency) -> Result<Option<bool>, DerpyError> {
+        Ok(None)
+    }
+
+    fn export(&self, log: &Log, root: &Path, dependency: &Dependency, dest: &Path) -> Result<(), DerpyError> {
+        export_dir(log, &dependency.get_full_path_in(root), dest, &[VENDORED_REVISION_FILE])
+    }
+
+    /// only the subdirectory wanted is ever vendored, so there is no checkout apart from it
+    fn get_checkout_dir(&self, root: &Path, dependency: &Dependency) -> PathBuf {
+        dependency.get_full_path_in(root)
+    }
+}
+
+/// A directory written by `derpy vendor`, holding a copy of each dependency in a directory of the
+/// same name, and a manifest in the lock file format recording the revision each was copied at.
+pub struct VendorDir {
+    dir: PathBuf,
+    manifest: LockFile,
+}
+
+impl VendorDir {
+    pub fn load(dir: PathBuf) -> Result<VendorDir, DerpyError> {
+        let manifest = load_lock(dir.join(VENDOR_MANIFEST_FILE))?;
+        Ok(VendorDir {
+            dir,
+            manifest,
+        })
+    }
+
+    /// Copies the dependency into place from this directory, which must have it at its locked
+    /// version (if it is locked). Only directories restored from a vendored copy are replaced.
+    pub fn restore(&self, log: &Log, root: &Path, dependency: &Dependency, locked_version: Option<&str>) -> Result<AcquireOutcome, DerpyError> {
+        let name = &dependency.name;
+        let source = self.dir.join(name);
+        let revision = match self.manifest.dependencies.get(name) {
+            Some(vendored) if source.is_dir() => vendored.revision.clone(),
+            _ => return Err(DerpyError::NotVendored {
+                name: name.clone(),
+                dir: self.dir.clone(),
+            }),
+        };
+        if let Some(locked_version) = locked_version {
+            if locked_version != revision {
+                return Err(DerpyError::VendoredVersionMismatch {
+                    name: name.clone(),
+                    locked_version: locked_version.into(),
+                    vendored_version: revision,
+                });
+            }
+        }
+
+        let dest = dependency.get_full_path_in(root);
+        let from_version = if dest.is_dir() {
+            if !is_vendored(root, dependency) {
+                return Err(DerpyError::DependencyInTheWay {
+                    name: name.clone(),
+                    path: dest,
+                });
+            }
+            let current_version = VendoredVcs.get_version_of(log, root, dependency)?;
+            if current_version == revision {
+                return Ok(AcquireOutcome::NoChange {
+                    current_version,
+                });
+            }
+            remove_dir(&dest)?;
+            Some(current_version)
+        } else {
+            None
+        };
+
+        ensure_dir(dependency.get_target_dir_in(root))?;
+        log.info(format!("copying {:?} to {:?}", source, dest));
+        if let Err(e) = copy_dir(&source, &dest, &[]) {
+            return Err(DerpyError::UnableToCopyDirectory {
+                path: source,
+                error: e,
+            });
+        }
+        let path = dest.join(VENDORED_REVISION_FILE);
+        if let Err(e) = File::create(&path).and_then(|mut file| file.write_all(revision.as_bytes())) {
+            return Err(DerpyError::UnableToWriteVendoredRevision {
+                path,
+                error: e,
+            });
+        }
+
+        Ok(match from_version {
+            Some(from_version) => AcquireOutcome::Restored {
+                from_version,
+                to_version: revision,
+            },
+            None => AcquireOutcome::Acquired {
+                at_version: revision,
+            },
+        })
+    }
+}
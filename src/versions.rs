@@ -0,0 +1,81 @@
+use std::path::Path;
+use semver::{Version, VersionReq};
+use dependency::Dependency;
+use events::Event;
+use error::DerpyError;
+use vcs::Vcs;
+use log::Log;
+
+/// Whether a dependency's version is a semver range, e.g. `^1.4`, to be resolved against its
+/// tags rather than a literal branch, tag or revision. Ranges must start with an operator, so
+/// that revision numbers such as `1234` are still taken literally.
+pub fn is_version_range(version: &str) -> bool {
+    version.starts_with(['^', '~', '<', '>', '=', '*'])
+}
+
+/// Reads a tag as a version, ignoring any leading `v` and filling in missing minor or patch
+/// numbers, so that `v1.4` is read as `1.4.0`. Returns `None` for tags that aren't versions.
+pub fn parse_tag(tag: &str) -> Option<Version> {
+    let text = tag.strip_prefix(['v', 'V']).unwrap_or(tag);
+    if let Ok(version) = Version::parse(text) {
+        return Some(version);
+    }
+    let parts = text.split('.').collect::<Vec<_>>();
+    if parts.len() < 3 && parts.iter().all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())) {
+        let mut padded = parts.join(".");
+        for _ in parts.len()..3 {
+            padded.push_str(".0");
+        }
+        return Version::parse(&padded).ok();
+    }
+    None
+}
+
+/// the tag with the highest version matching `req`, or the highest release of all if `req` is `None`
+pub fn highest_matching<'a>(tags: &'a [String], req: Option<&VersionReq>) -> Option<&'a str> {
+    tags.iter()
+        .filter_map(|tag| parse_tag(tag).map(|version| (version, tag)))
+        .filter(|(version, _)| match req {
+            Some(req) => req.matches(version),
+            None => version.pre.is_empty(),
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, tag)| tag.as_str())
+}
+
+pub fn parse_version_range(dep: &Dependency) -> Result<VersionReq, DerpyError> {
+    match VersionReq::parse(&dep.version) {
+        Ok(req) => Ok(req),
+        Err(e) => Err(DerpyError::InvalidVersionRange {
+            name: dep.name.clone(),
+            range: dep.version.clone(),
+            error: e.to_string(),
+        }),
+    }
+}
+
+/// Picks the tag to use for a dependency whose version is a range: the highest within the range,
+/// or the highest of all when `breaking` changes are allowed.
+pub fn resolve_version_range(log: &Log, vcs: &dyn Vcs, root: &Path, dep: &Dependency, breaking: bool) -> Result<String, DerpyError> {
+    let req = parse_version_range(dep)?;
+    let tags = match vcs.list_versions(log, root, dep)? {
+        Some(tags) => tags,
+        None => return Err(DerpyError::UnableToListVersions {
+            name: dep.name.clone(),
+            vcs: dep.vcs.clone(),
+        }),
+    };
+    let tag = match highest_matching(&tags, if breaking { None } else { Some(&req) }) {
+        Some(tag) => tag.to_string(),
+        None => return Err(DerpyError::NoMatchingVersion {
+            name: dep.name.clone(),
+            range: dep.version.clone(),
+        }),
+    };
+    log.event(Event::VersionResolved {
+        name: dep.name.clone(),
+        range: dep.version.clone(),
+        tag: tag.clone(),
+    });
+    Ok(tag)
+}
@@ -0,0 +1,155 @@
+//! Exercises the native archive backend against archives created in a temporary directory.
+
+extern crate flate2;
+extern crate serde_json;
+extern crate sha2;
+extern crate tar;
+extern crate tempfile;
+extern crate xz2;
+extern crate zip;
+
+mod common;
+
+use std::fs::{create_dir_all, read, remove_dir_all, File};
+use std::io::Write;
+use std::path::Path;
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+use xz2::write::XzEncoder;
+use common::{file_url, Project};
+
+/// the files in each archive, all inside a top level directory as is usual for source releases
+fn files(text: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("lib-1.0/a.txt", text.to_string()),
+        ("lib-1.0/sub/b.txt", "b".to_string()),
+    ]
+}
+
+fn write_tar<W: Write>(writer: W, text: &str) -> W {
+    let mut builder = tar::Builder::new(writer);
+    for (path, contents) in files(text) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, path, contents.as_bytes()).unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+fn write_tar_gz(path: &Path, text: &str) {
+    write_tar(GzEncoder::new(File::create(path).unwrap(), flate2::Compression::default()), text)
+        .finish()
+        .unwrap();
+}
+
+fn write_tar_xz(path: &Path, text: &str) {
+    write_tar(XzEncoder::new(File::create(path).unwrap(), 6), text)
+        .finish()
+        .unwrap();
+}
+
+fn write_zip(path: &Path, text: &str) {
+    let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+    for (path, contents) in files(text) {
+        writer.start_file(path, zip::write::FileOptions::default()).unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+fn sha256_of(path: &Path) -> String {
+    format!("{:x}", Sha256::digest(read(path).unwrap()))
+}
+
+#[test]
+fn tar_gz_is_locked_to_its_hash() {
+    let project = Project::new();
+    let archive = project.scratch().join("lib.tar.gz");
+    write_tar_gz(&archive, "first");
+
+    project.derpy_ok(&["add", "archive", "lib", &file_url(&archive), "--option", "strip_components:1"]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    assert_eq!(project.dependency_file("lib", "sub/b.txt"), "b");
+    assert_eq!(project.locked_revision("lib"), sha256_of(&archive));
+
+    // the archive changing under the lock file must not go unnoticed
+    write_tar_gz(&archive, "second");
+    remove_dir_all(project.root().join("deps/lib")).unwrap();
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "ArchiveChecksumMismatch", "{}", error);
+
+    project.derpy_ok(&["upgrade", "--all"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "second");
+    assert_eq!(project.locked_revision("lib"), sha256_of(&archive));
+}
+
+#[test]
+fn tar_xz_without_stripping() {
+    let project = Project::new();
+    let archive = project.scratch().join("lib.tar.xz");
+    write_tar_xz(&archive, "first");
+
+    project.derpy_ok(&["add", "archive", "lib", archive.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "lib-1.0/a.txt"), "first");
+}
+
+#[test]
+fn zip_is_checked_against_sha256_option() {
+    let project = Project::new();
+    let archive = project.scratch().join("lib.zip");
+    write_zip(&archive, "first");
+    let hash = sha256_of(&archive);
+
+    project.derpy_ok(&["add", "archive", "lib", &file_url(&archive),
+        "--option", "strip_components:1", "--option", &format!("sha256:{}", hash)]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    assert_eq!(project.locked_revision("lib"), hash);
+
+    // an archive that doesn't match the config is refused before anything is extracted
+    write_zip(&archive, "second");
+    let error = project.derpy_err(&["upgrade", "--all"]);
+    assert_eq!(error["error"]["kind"], "ArchiveChecksumMismatch", "{}", error);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+}
+
+/// writes a `.tar.gz` with a symlink at `lib/link` to `link_target`, then a file beneath it
+fn write_tar_gz_through_symlink(path: &Path, link_target: &Path) {
+    let mut builder = tar::Builder::new(GzEncoder::new(File::create(path).unwrap(), flate2::Compression::default()));
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Symlink);
+    header.set_size(0);
+    header.set_mode(0o777);
+    builder.append_link(&mut header, "lib/link", link_target).unwrap();
+
+    let contents = "escaped";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, "lib/link/escaped.txt", contents.as_bytes()).unwrap();
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+#[test]
+fn tar_entries_cannot_escape_through_symlinks() {
+    for relative in [false, true] {
+        let project = Project::new();
+        let outside = project.scratch().join("outside");
+        create_dir_all(&outside).unwrap();
+        let link_target = if relative { Path::new("../../../outside").to_path_buf() } else { outside.clone() };
+
+        let archive = project.scratch().join("lib.tar.gz");
+        write_tar_gz_through_symlink(&archive, &link_target);
+        project.derpy_ok(&["add", "archive", "lib", &file_url(&archive), "--option", "strip_components:1"]);
+
+        let error = project.derpy_err(&["acquire"]);
+        assert_eq!(error["error"]["kind"], "UnableToExtractArchive", "{}", error);
+        assert!(!outside.join("escaped.txt").exists());
+        assert!(!project.root().join("deps/lib").exists());
+    }
+}
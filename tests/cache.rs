@@ -0,0 +1,93 @@
+//! Exercises the shared cache of mirrored repositories.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{read_dir, rename};
+use std::path::Path;
+use std::thread;
+use serde_json::json;
+use tempfile::TempDir;
+use common::{create_repo, run, Project};
+
+/// how many git mirrors there are in `cache`, leaving out their lock files
+fn mirrors(cache: &Path) -> usize {
+    read_dir(cache.join("git")).unwrap().filter(|entry| entry.as_ref().unwrap().path().is_dir()).count()
+}
+
+#[test]
+fn git_mirror_is_shared_between_projects() {
+    require_tool!("git", "--version");
+
+    let shared = TempDir::new().unwrap();
+    let cache = shared.path().join("cache");
+    let repo = shared.path().join("lib");
+    create_repo(&repo, "first");
+    let url = repo.to_str().unwrap();
+
+    let first = Project::new().with_env("DERPY_CACHE", cache.to_str().unwrap());
+    first.derpy_ok(&["add", "git", "lib", url]);
+    first.derpy_ok(&["acquire"]);
+    assert_eq!(first.dependency_file("lib", "a.txt"), "first");
+    assert_eq!(mirrors(&cache), 1);
+    let origin = run(&first.root().join("deps/lib"), &[], "git", &["remote", "get-url", "origin"]);
+    assert_eq!(origin.trim(), url);
+
+    // with the original out of reach, the second project can still be acquired from the mirror
+    rename(&repo, shared.path().join("moved")).unwrap();
+    let second = Project::new().with_env("DERPY_CACHE", cache.to_str().unwrap());
+    second.derpy_ok(&["add", "git", "lib", url]);
+    let events = second.derpy_ok(&["acquire"]);
+    assert!(events.iter().any(|event| event["event"] == "mirror_update_failed"));
+    assert_eq!(second.dependency_file("lib", "a.txt"), "first");
+}
+
+#[test]
+fn cache_is_off_unless_asked_for() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("lib");
+    create_repo(&repo, "first");
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    let events = project.derpy_ok(&["acquire"]);
+    let acquired = events.iter().find(|event| event["event"] == "acquire").unwrap();
+    assert_eq!(acquired["commands"][0], json!(["git", "clone", repo.to_str().unwrap(), "lib"]));
+    assert!(!project.scratch().join("cache").exists());
+}
+
+#[test]
+fn parallel_jobs_share_one_mirror() {
+    require_tool!("git", "--version");
+
+    let shared = TempDir::new().unwrap();
+    let cache = shared.path().join("cache");
+    let repo = shared.path().join("lib");
+    create_repo(&repo, "first");
+    let url = repo.to_str().unwrap().to_string();
+    let names = ["one", "two", "three", "four"];
+
+    // several jobs in each of two projects all want the same mirror at once
+    let projects = (0..2).map(|_| {
+        let project = Project::new().with_env("DERPY_CACHE", cache.to_str().unwrap());
+        for name in names.iter() {
+            project.derpy_ok(&["add", "git", name, &url]);
+        }
+        project
+    }).collect::<Vec<_>>();
+    let handles = projects.into_iter().map(|project| thread::spawn(move || {
+        project.derpy_ok(&["acquire", "-j", "4"]);
+        project
+    })).collect::<Vec<_>>();
+
+    for handle in handles {
+        let project = handle.join().unwrap();
+        for name in names.iter() {
+            assert_eq!(project.dependency_file(name, "a.txt"), "first");
+        }
+    }
+    assert_eq!(mirrors(&cache), 1);
+}
@@ -0,0 +1,178 @@
+//! Helpers shared by the integration tests, which run the derpy binary against projects
+//! created in a temporary directory.
+#![allow(dead_code, unused_macros)]
+
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serde_json::Value;
+use tempfile::TempDir;
+
+pub fn tool_available(program: &str, version_arg: &str) -> bool {
+    Command::new(program)
+        .arg(version_arg)
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+macro_rules! require_tool {
+    ($program:expr, $version_arg:expr) => {
+        if !::common::tool_available($program, $version_arg) {
+            eprintln!("skipping, {} is not installed", $program);
+            return;
+        }
+    };
+}
+
+/// runs a command in the given directory, returning its stdout and panicking if it fails
+pub fn run(dir: &Path, envs: &[(&str, &str)], program: &str, args: &[&str]) -> String {
+    let output = Command::new(program)
+        .args(args)
+        .envs(envs.iter().cloned())
+        .current_dir(dir)
+        .output()
+        .unwrap_or_else(|e| panic!("unable to run {} {:?}: {}", program, args, e));
+    if !output.status.success() {
+        panic!("{} {:?} failed: stdout='{}', stderr='{}'", program, args,
+            String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    }
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// the identity commits in test repositories are made with, so that git never has to ask for one
+pub const GIT_ENVS: &[(&str, &str)] = &[
+    ("GIT_AUTHOR_NAME", "test"), ("GIT_AUTHOR_EMAIL", "test@example.com"),
+    ("GIT_COMMITTER_NAME", "test"), ("GIT_COMMITTER_EMAIL", "test@example.com"),
+];
+
+/// creates a git repository with `a.txt` holding `contents` committed, returning its revision
+pub fn create_repo(repo: &Path, contents: &str) -> String {
+    create_dir_all(repo).unwrap();
+    run(repo, GIT_ENVS, "git", &["init", "-q"]);
+    commit(repo, "a.txt", contents)
+}
+
+/// writes `contents` to `file` in a git repository and commits it, returning the new revision
+pub fn commit(repo: &Path, file: &str, contents: &str) -> String {
+    let path = repo.join(file);
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).unwrap();
+    }
+    write(&path, contents).unwrap();
+    run(repo, GIT_ENVS, "git", &["add", file]);
+    run(repo, GIT_ENVS, "git", &["commit", "-q", "-m", file]);
+    run(repo, &[], "git", &["rev-parse", "HEAD"]).trim().to_string()
+}
+
+/// rewrites a JSON file such as derpy.json with the changes `edit` makes to it
+pub fn edit_json(path: &Path, edit: &dyn Fn(&mut Value)) {
+    let mut value: Value = serde_json::from_str(&read_to_string(path).unwrap()).unwrap();
+    edit(&mut value);
+    write(path, serde_json::to_string_pretty(&value).unwrap()).unwrap();
+}
+
+pub struct Project {
+    temp: TempDir,
+    envs: Vec<(String, String)>,
+    exe: PathBuf,
+}
+
+impl Project {
+    pub fn new() -> Project {
+        let project = Project {
+            temp: TempDir::new().expect("unable to create temp dir"),
+            envs: Vec::new(),
+            exe: PathBuf::from(env!("CARGO_BIN_EXE_derpy")),
+        };
+        create_dir_all(project.root()).unwrap();
+        project.derpy_ok(&["init"]);
+        project
+    }
+
+    pub fn with_env(mut self, key: &str, value: &str) -> Project {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    /// runs derpy from `exe` from now on, e.g. a copy of it installed somewhere else
+    pub fn with_exe(mut self, exe: &Path) -> Project {
+        self.exe = exe.to_path_buf();
+        self
+    }
+
+    /// scratch space for creating repositories in, outside of the project
+    pub fn scratch(&self) -> PathBuf {
+        self.temp.path().to_path_buf()
+    }
+
+    pub fn root(&self) -> PathBuf {
+        self.temp.path().join("project")
+    }
+
+    /// runs derpy against the project, returning the events it printed
+    pub fn derpy(&self, args: &[&str]) -> Vec<Value> {
+        self.derpy_status(args).1
+    }
+
+    /// runs derpy against the project, returning whether it succeeded and the events it printed
+    fn derpy_status(&self, args: &[&str]) -> (bool, Vec<Value>) {
+        let root = self.root();
+        let config_home = self.temp.path().join("config");
+        let cache_home = self.temp.path().join("cache");
+        let output = Command::new(&self.exe)
+            .arg("--path").arg(&root)
+            .args(["--format", "json"])
+            .args(args)
+            .env("XDG_CONFIG_HOME", &config_home)
+            .env("XDG_CACHE_HOME", &cache_home)
+            .env_remove("DERPY_VCS_PATH")
+            .env_remove("DERPY_CACHE")
+            .envs(self.envs.iter().cloned())
+            .output()
+            .expect("unable to run derpy");
+        let events = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("bad event '{}': {}", line, e)))
+            .collect();
+        (output.status.success(), events)
+    }
+
+    pub fn derpy_ok(&self, args: &[&str]) -> Vec<Value> {
+        let (success, events) = self.derpy_status(args);
+        if let Some(error) = events.iter().find(|event| event["event"] == "error") {
+            panic!("derpy {:?} failed: {}", args, error);
+        }
+        assert!(success, "derpy {:?} exited with an error status: {:?}", args, events);
+        events
+    }
+
+    pub fn derpy_err(&self, args: &[&str]) -> Value {
+        let (success, events) = self.derpy_status(args);
+        assert!(!success, "derpy {:?} reported an error but exited successfully: {:?}", args, events);
+        match events.into_iter().find(|event| event["event"] == "error") {
+            Some(error) => error,
+            None => panic!("derpy {:?} unexpectedly succeeded", args),
+        }
+    }
+
+    pub fn locked_revision(&self, name: &str) -> String {
+        let lock: Value = serde_json::from_str(&read_to_string(self.root().join("derpy.lock.json")).unwrap()).unwrap();
+        lock["dependencies"][name]["revision"].as_str()
+            .unwrap_or_else(|| panic!("'{}' is not locked", name))
+            .to_string()
+    }
+
+    /// rewrites the project's derpy.json with the changes `edit` makes to it
+    pub fn edit_config(&self, edit: &dyn Fn(&mut Value)) {
+        edit_json(&self.root().join("derpy.json"), edit);
+    }
+
+    pub fn dependency_file(&self, name: &str, file: &str) -> String {
+        read_to_string(self.root().join("deps").join(name).join(file)).unwrap()
+    }
+}
+
+pub fn file_url(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
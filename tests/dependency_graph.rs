@@ -0,0 +1,268 @@
+//! Exercises dependencies of dependencies, read from the derpy.json of each one acquired.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::read_to_string;
+use std::path::Path;
+use serde_json::{json, Value};
+use common::{commit, create_repo, run, Project};
+
+fn dependency(name: &str, repo: &Path, version: &str) -> Value {
+    json!({
+        "name": name,
+        "vcs": "git",
+        "url": repo.to_str().unwrap(),
+        "version": version,
+        "target": "deps/",
+        "options": {},
+    })
+}
+
+/// creates a repository in the project's scratch space requiring `dependencies`
+fn library(project: &Project, name: &str, dependencies: &[Value]) -> String {
+    let repo = project.scratch().join(name);
+    create_repo(&repo, name);
+    require(&repo, dependencies)
+}
+
+/// commits a derpy.json to `repo` requiring `dependencies`
+fn require(repo: &Path, dependencies: &[Value]) -> String {
+    let config = json!({
+        "dependencies": dependencies.iter()
+            .map(|dep| (dep["name"].as_str().unwrap().to_string(), dep.clone()))
+            .collect::<serde_json::Map<_, _>>(),
+    });
+    commit(repo, "derpy.json", &serde_json::to_string_pretty(&config).unwrap())
+}
+
+fn lock(project: &Project) -> Value {
+    serde_json::from_str(&read_to_string(project.root().join("derpy.lock.json")).unwrap()).unwrap()
+}
+
+fn acquisitions<'a>(events: &'a [Value], name: &str) -> Vec<&'a Value> {
+    events.iter().filter(|event| event["event"] == "acquire" && event["name"] == name).collect()
+}
+
+#[test]
+fn dependencies_of_dependencies_are_acquired() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let leaf_revision = library(&project, "leaf", &[]);
+    library(&project, "middle", &[dependency("leaf", &project.scratch().join("leaf"), "master")]);
+    project.derpy_ok(&["add", "git", "middle", project.scratch().join("middle").to_str().unwrap()]);
+
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("leaf", "a.txt"), "leaf");
+    assert_eq!(project.locked_revision("leaf"), leaf_revision);
+    assert_eq!(lock(&project)["dependencies"]["leaf"]["required_by"], json!(["middle"]));
+    assert_eq!(lock(&project)["dependencies"]["middle"]["required_by"], json!([null]));
+}
+
+#[test]
+fn shared_dependency_is_acquired_once() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let shared = project.scratch().join("shared");
+    library(&project, "shared", &[]);
+    library(&project, "left", &[dependency("shared", &shared, "master")]);
+    library(&project, "right", &[dependency("shared", &shared, "master")]);
+    project.derpy_ok(&["add", "git", "left", project.scratch().join("left").to_str().unwrap()]);
+    project.derpy_ok(&["add", "git", "right", project.scratch().join("right").to_str().unwrap()]);
+
+    let events = project.derpy_ok(&["acquire"]);
+    assert_eq!(acquisitions(&events, "shared").len(), 1);
+    assert_eq!(acquisitions(&events, "shared")[0]["outcome"]["kind"], "acquired");
+    assert_eq!(lock(&project)["dependencies"]["shared"]["required_by"], json!(["left", "right"]));
+}
+
+#[test]
+fn dependency_cycle_is_an_error() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let first = project.scratch().join("first");
+    let second = project.scratch().join("second");
+    library(&project, "first", &[dependency("second", &second, "master")]);
+    library(&project, "second", &[dependency("first", &first, "master")]);
+    project.derpy_ok(&["add", "git", "first", first.to_str().unwrap()]);
+
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "DependencyCycle", "{}", error);
+    assert!(error["error"]["message"].as_str().unwrap().contains(r#"["first", "second", "first"]"#), "{}", error);
+    assert!(!project.root().join("derpy.lock.json").exists());
+}
+
+#[test]
+fn project_requirement_wins_over_its_dependencies() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let shared = project.scratch().join("shared");
+    let fork = project.scratch().join("fork");
+    library(&project, "shared", &[]);
+    library(&project, "fork", &[]);
+    library(&project, "left", &[dependency("shared", &shared, "master")]);
+    library(&project, "right", &[dependency("shared", &shared, "master")]);
+    project.derpy_ok(&["add", "git", "left", project.scratch().join("left").to_str().unwrap()]);
+    project.derpy_ok(&["add", "git", "right", project.scratch().join("right").to_str().unwrap()]);
+    project.derpy_ok(&["add", "git", "shared", fork.to_str().unwrap()]);
+
+    let events = project.derpy_ok(&["acquire"]);
+    let overridden = events.iter()
+        .filter(|event| event["event"] == "requirement_overridden" && event["name"] == "shared")
+        .collect::<Vec<_>>();
+    assert_eq!(overridden.len(), 2);
+    assert!(overridden.iter().all(|event| event["url"] == shared.to_str().unwrap() && event["chosen_url"] == fork.to_str().unwrap()));
+    assert_eq!(project.dependency_file("shared", "a.txt"), "fork");
+    assert_eq!(lock(&project)["dependencies"]["shared"]["url"], fork.to_str().unwrap());
+    assert_eq!(lock(&project)["dependencies"]["shared"]["required_by"], json!([null, "left", "right"]));
+}
+
+#[test]
+fn conflicting_requirements_name_both_requesters() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let shared = project.scratch().join("shared");
+    let fork = project.scratch().join("fork");
+    library(&project, "shared", &[]);
+    library(&project, "fork", &[]);
+    library(&project, "left", &[dependency("shared", &shared, "master")]);
+    library(&project, "right", &[dependency("shared", &fork, "master")]);
+    project.derpy_ok(&["add", "git", "left", project.scratch().join("left").to_str().unwrap()]);
+    project.derpy_ok(&["add", "git", "right", project.scratch().join("right").to_str().unwrap()]);
+
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "DependencyConflict", "{}", error);
+    let message = error["error"]["message"].as_str().unwrap();
+    assert!(message.contains(&format!("'left' wants master ({})", shared.display())), "{}", message);
+    assert!(message.contains(&format!("'right' wants master ({})", fork.display())), "{}", message);
+    assert_eq!(error["error"]["name"], "shared");
+    assert_eq!(error["error"]["first"], json!({"requester": "'left'", "url": shared.to_str().unwrap(), "version": "master"}));
+    assert_eq!(error["error"]["second"], json!({"requester": "'right'", "url": fork.to_str().unwrap(), "version": "master"}));
+    assert!(!project.root().join("deps/shared").exists());
+}
+
+#[test]
+fn external_target_needs_the_project_to_allow_it() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("lib");
+    create_repo(&repo, "lib");
+    let error = project.derpy_err(&["add", "git", "lib", repo.to_str().unwrap(), "--target", "../outside"]);
+    assert_eq!(error["error"]["kind"], "DependencyOutsideProject", "{}", error);
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap(), "--target", "../outside", "--allow-external"]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(read_to_string(project.scratch().join("outside/lib/a.txt")).unwrap(), "lib");
+
+    // a target edited by hand is checked too, so the opt-in has to be kept with it
+    project.edit_config(&|config| {
+        config["dependencies"]["lib"]["target"] = "../elsewhere".into();
+        config["dependencies"]["lib"].as_object_mut().unwrap().remove("allow_external");
+    });
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "DependencyOutsideProject", "{}", error);
+    assert!(!project.scratch().join("elsewhere").exists());
+}
+
+#[test]
+fn dependency_name_must_be_a_single_directory() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("lib");
+    create_repo(&repo, "lib");
+    for name in ["../../../pwn", "a/b", ".", "/abs"].iter() {
+        let error = project.derpy_err(&["add", "git", name, repo.to_str().unwrap(), "--target", "deps/a/b/c", "--subdir", "sub"]);
+        assert_eq!(error["error"]["kind"], "InvalidDependencyName", "{}", error);
+    }
+
+    // nor can a dependency's own derpy.json get around it
+    library(&project, "middle", &[dependency("../../pwn", &repo, "master")]);
+    project.derpy_ok(&["add", "git", "middle", project.scratch().join("middle").to_str().unwrap()]);
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "InvalidDependencyName", "{}", error);
+    assert!(!project.scratch().join("pwn").exists());
+    assert!(!project.scratch().join("pwn.json").exists());
+}
+
+#[test]
+fn upgrade_acquires_new_dependencies() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let middle = project.scratch().join("middle");
+    library(&project, "leaf", &[]);
+    library(&project, "middle", &[]);
+    project.derpy_ok(&["add", "git", "middle", middle.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+
+    // the upgraded dependency now requires one that has never been acquired
+    let revision = require(&middle, &[dependency("leaf", &project.scratch().join("leaf"), "master")]);
+    project.edit_config(&|config| config["dependencies"]["middle"]["version"] = revision.clone().into());
+    let events = project.derpy_ok(&["upgrade", "--all"]);
+    assert_eq!(acquisitions(&events, "leaf")[0]["outcome"]["kind"], "acquired", "{:?}", events);
+    assert_eq!(project.dependency_file("leaf", "a.txt"), "leaf");
+    assert_eq!(lock(&project)["dependencies"]["leaf"]["required_by"], json!(["middle"]));
+    assert_eq!(project.locked_revision("middle"), revision);
+}
+
+#[test]
+fn parallel_jobs_keep_each_dependency_together() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let names = ["one", "two", "three", "four"];
+    for name in names.iter() {
+        let leaf = format!("{}_leaf", name);
+        library(&project, &leaf, &[]);
+        library(&project, name, &[dependency(&leaf, &project.scratch().join(&leaf), "master")]);
+        project.derpy_ok(&["add", "git", name, project.scratch().join(name).to_str().unwrap()]);
+        // so that the jobs are sure to overlap
+        project.edit_config(&|config| config["dependencies"][name]["hooks"] = json!({ "post_acquire": [["sleep", "0.2"]] }));
+    }
+
+    // text output, as that is where the commands run for each dependency are shown
+    let root = project.root();
+    let output = run(&root, &[], env!("CARGO_BIN_EXE_derpy"), &["--path", root.to_str().unwrap(), "-vv", "acquire", "-j", "4"]);
+    for name in names.iter() {
+        let leaf = format!("{}_leaf", name);
+        assert_eq!(project.dependency_file(name, "a.txt"), *name);
+        assert_eq!(project.dependency_file(&leaf, "a.txt"), leaf);
+        assert_eq!(lock(&project)["dependencies"][&leaf]["required_by"], json!([name]));
+    }
+
+    // each dependency's output ends with its outcome, and nothing from another job is mixed into it
+    let mut acquired = Vec::new();
+    let mut dirs = Vec::new();
+    for line in output.lines() {
+        if let Some(dir) = line.trim().strip_prefix("(in dir ") {
+            dirs.push(dir.trim_matches(|c| c == '(' || c == ')' || c == '"').to_string());
+        } else if let Some(outcome) = line.strip_prefix("- acquired '") {
+            let name = outcome.split('\'').next().unwrap().to_string();
+            let own_dir = root.join("deps").join(&name);
+            let dependency_dirs = dirs.iter()
+                .map(Path::new)
+                .filter(|dir| dir.parent() == Some(&root.join("deps")))
+                .collect::<Vec<_>>();
+            assert!(!dependency_dirs.is_empty(), "{}", output);
+            assert!(dependency_dirs.iter().all(|dir| *dir == own_dir), "output of '{}' is mixed up with others:\n{}", name, output);
+            dirs.clear();
+            acquired.push(name);
+        }
+    }
+    acquired.sort();
+    let mut expected = names.iter().map(|name| name.to_string())
+        .chain(names.iter().map(|name| format!("{}_leaf", name)))
+        .collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(acquired, expected, "{}", output);
+}
@@ -0,0 +1,98 @@
+//! Exercises `--dry-run`, which plans what acquire and upgrade would do without doing any of it.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{read_to_string, remove_dir_all};
+use serde_json::{json, Value};
+use common::{commit, create_repo, run, Project};
+
+fn planned(events: &[Value], name: &str) -> Value {
+    events.iter()
+        .find(|event| event["event"] == "planned" && event["name"] == name)
+        .unwrap_or_else(|| panic!("no plan for '{}' in {:?}", name, events))
+        .clone()
+}
+
+#[test]
+fn dry_run_acquire_plans_without_acquiring() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let cache = project.scratch().join("cache");
+    let project = project.with_env("DERPY_CACHE", cache.to_str().unwrap());
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "first");
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    project.edit_config(&|config| {
+        config["dependencies"]["lib"]["hooks"] = json!({ "post_acquire": [["make", "{DEP_NAME}"]] });
+    });
+
+    let events = project.derpy_ok(&["acquire", "--dry-run"]);
+    let plan = planned(&events, "lib");
+    let actions = plan["actions"].as_array().unwrap();
+    assert_eq!(actions[0]["kind"], "acquire");
+    let mirror_command = actions[0]["commands"][0].as_array().unwrap();
+    assert_eq!(mirror_command[..5], [json!("git"), json!("clone"), json!("--mirror"), json!("--quiet"), json!(repo.to_str().unwrap())]);
+    assert_eq!(actions[1], json!({ "kind": "run_hook", "hook": "post_acquire", "commands": [["make", "lib"]] }));
+    assert_eq!(plan["lock_change"], json!({ "kind": "added", "revision": null }));
+
+    assert!(!project.root().join("deps/lib").exists());
+    assert!(!project.root().join("derpy.lock.json").exists());
+    assert!(!cache.exists());
+}
+
+#[test]
+fn dry_run_upgrade_leaves_checkout_and_lock_file_alone() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "first");
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    let revision = project.locked_revision("lib");
+    let lock_before = read_to_string(project.root().join("derpy.lock.json")).unwrap();
+    commit(&repo, "a.txt", "second");
+
+    // already at its locked version, so there is nothing to do
+    let events = project.derpy_ok(&["acquire", "--dry-run"]);
+    let plan = planned(&events, "lib");
+    assert_eq!(plan["actions"], json!([]));
+    assert_eq!(plan["lock_change"], Value::Null);
+
+    let events = project.derpy_ok(&["upgrade", "lib", "--dry-run"]);
+    let plan = planned(&events, "lib");
+    assert_eq!(plan["actions"], json!([
+        { "kind": "upgrade", "commands": [["git", "fetch"], ["git", "checkout", "master"]] },
+    ]));
+    assert_eq!(plan["lock_change"], json!({ "kind": "updated", "from_revision": revision, "to_revision": null }));
+    assert!(!events.iter().any(|event| event["event"] == "lock_file_updated"));
+
+    assert_eq!(read_to_string(project.root().join("derpy.lock.json")).unwrap(), lock_before);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    assert_eq!(run(&project.root().join("deps/lib"), &[], "git", &["rev-parse", "HEAD"]).trim(), revision);
+}
+
+#[test]
+fn dry_run_upgrade_plans_to_acquire_a_missing_checkout() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "first");
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    remove_dir_all(project.root().join("deps/lib")).unwrap();
+
+    let events = project.derpy_ok(&["upgrade", "lib", "--dry-run"]);
+    let plan = planned(&events, "lib");
+    assert_eq!(plan["actions"][0]["kind"], "acquire", "{}", plan);
+    assert!(!project.root().join("deps/lib").exists());
+}
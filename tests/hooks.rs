@@ -0,0 +1,54 @@
+//! Exercises the hooks run after a dependency is acquired, upgraded or checked out.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::remove_file;
+use serde_json::{json, Value};
+use common::{create_repo, Project};
+
+fn set_hooks(project: &Project, name: &str, hooks: Value) {
+    project.edit_config(&|config| config["dependencies"][name]["hooks"] = hooks.clone());
+}
+
+#[test]
+fn hooks_run_in_the_dependency_directory() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "first");
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    set_hooks(&project, "lib", json!({
+        "post_acquire": [["sh", "-c", "printf %s \"$1\" > generated.txt", "sh", "{DEP_NAME} {DEP_REVISION}"]],
+    }));
+    project.derpy_ok(&["acquire"]);
+    let revision = project.locked_revision("lib");
+    assert_eq!(project.dependency_file("lib", "generated.txt"), format!("lib {}", revision));
+
+    // nothing changes, so no hook runs
+    remove_file(project.root().join("deps/lib/generated.txt")).unwrap();
+    project.derpy_ok(&["acquire"]);
+    assert!(!project.root().join("deps/lib/generated.txt").exists());
+}
+
+#[test]
+fn failing_hook_is_named() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "first");
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    set_hooks(&project, "lib", json!({
+        "post_acquire": [["sh", "-c", "exit 3"]],
+    }));
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "HookFailed", "{}", error);
+    assert!(error["error"]["message"].as_str().unwrap().starts_with("post_acquire hook of dependency 'lib'"));
+}
@@ -0,0 +1,96 @@
+//! Exercises how the lock file follows changes to the config it was locked from.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{read_to_string, remove_dir_all};
+use serde_json::Value;
+use common::{create_repo, Project};
+
+fn lock(project: &Project) -> Value {
+    serde_json::from_str(&read_to_string(project.root().join("derpy.lock.json")).unwrap()).unwrap()
+}
+
+fn locked_url(project: &Project, name: &str) -> Value {
+    lock(project)["dependencies"][name]["url"].clone()
+}
+
+#[test]
+fn edited_url_acquires_from_the_new_url() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    let other = project.scratch().join("other");
+    let revision = create_repo(&repo, "original");
+    let other_revision = create_repo(&other, "elsewhere");
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+
+    let url = other.to_str().unwrap().to_string();
+    project.edit_config(&|config| config["dependencies"]["lib"]["url"] = url.clone().into());
+    let events = project.derpy_ok(&["acquire"]);
+    assert!(events.iter().any(|event| event["event"] == "lock_entry_changed" && event["locked_version"] == revision.as_str()));
+    assert!(events.iter().any(|event| event["event"] == "checkout_replaced"));
+    assert_eq!(project.dependency_file("lib", "a.txt"), "elsewhere");
+    assert_eq!(project.locked_revision("lib"), other_revision);
+    assert_eq!(locked_url(&project, "lib"), url.as_str());
+
+    // without a record of where the checkout came from, it is taken to be from the locked url
+    remove_dir_all(project.root().join(".derpy/sources")).unwrap();
+    let url = repo.to_str().unwrap().to_string();
+    project.edit_config(&|config| config["dependencies"]["lib"]["url"] = url.clone().into());
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "original");
+    assert_eq!(project.locked_revision("lib"), revision);
+}
+
+#[test]
+fn changed_options_unlock_the_dependency() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    let revision = create_repo(&repo, "first");
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    let config_hash = lock(&project)["dependencies"]["lib"]["config_hash"].clone();
+
+    let events = project.derpy_ok(&["acquire"]);
+    assert!(!events.iter().any(|event| event["event"] == "lock_entry_changed"));
+
+    project.edit_config(&|config| config["dependencies"]["lib"]["options"]["depth"] = "1".into());
+    let events = project.derpy_ok(&["acquire"]);
+    assert!(events.iter().any(|event| event["event"] == "lock_entry_changed" && event["name"] == "lib"));
+    assert_eq!(project.locked_revision("lib"), revision);
+    assert_ne!(lock(&project)["dependencies"]["lib"]["config_hash"], config_hash);
+
+    let events = project.derpy_ok(&["acquire"]);
+    assert!(!events.iter().any(|event| event["event"] == "lock_entry_changed"));
+}
+
+#[test]
+fn removed_dependency_leaves_the_lock_file() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let first = project.scratch().join("first");
+    let second = project.scratch().join("second");
+    create_repo(&first, "first");
+    let revision = create_repo(&second, "second");
+    project.derpy_ok(&["add", "git", "first", first.to_str().unwrap()]);
+    project.derpy_ok(&["add", "git", "second", second.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    assert!(lock(&project)["dependencies"]["first"].is_object());
+
+    project.edit_config(&|config| {
+        config["dependencies"].as_object_mut().unwrap().remove("first");
+    });
+    let events = project.derpy_ok(&["acquire"]);
+    assert!(events.iter().any(|event| event["event"] == "no_longer_required" && event["name"] == "first"));
+    assert!(lock(&project)["dependencies"].get("first").is_none());
+    assert_eq!(project.locked_revision("second"), revision);
+}
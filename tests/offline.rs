@@ -0,0 +1,59 @@
+//! Exercises `--offline`, which must never run a command that could use the network.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{remove_dir_all, rename};
+use tempfile::TempDir;
+use common::{create_repo, Project};
+
+#[test]
+fn offline_lists_every_missing_dependency() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let first = project.scratch().join("first");
+    let second = project.scratch().join("second");
+    create_repo(&first, "first");
+    create_repo(&second, "first");
+    project.derpy_ok(&["add", "git", "first", first.to_str().unwrap()]);
+    project.derpy_ok(&["add", "git", "second", second.to_str().unwrap()]);
+
+    let error = project.derpy_err(&["--offline", "acquire"]);
+    assert_eq!(error["error"]["kind"], "MissingOffline");
+    let message = error["error"]["message"].as_str().unwrap();
+    assert!(message.contains("'first'"), "{}", message);
+    assert!(message.contains("'second'"), "{}", message);
+    let missing = error["error"]["dependencies"].as_array().unwrap();
+    assert_eq!(missing.iter().map(|dep| dep["name"].as_str().unwrap()).collect::<Vec<_>>(), ["first", "second"]);
+    assert!(!project.root().join("deps/first").exists());
+}
+
+#[test]
+fn offline_acquires_locked_revision_from_the_cache() {
+    require_tool!("git", "--version");
+
+    let shared = TempDir::new().unwrap();
+    let cache = shared.path().join("cache");
+    let repo = shared.path().join("lib");
+    create_repo(&repo, "first");
+    let url = repo.to_str().unwrap();
+
+    let project = Project::new().with_env("DERPY_CACHE", cache.to_str().unwrap());
+    project.derpy_ok(&["add", "git", "lib", url]);
+    project.derpy_ok(&["acquire"]);
+    let locked = project.locked_revision("lib");
+
+    rename(&repo, shared.path().join("moved")).unwrap();
+    remove_dir_all(project.root().join("deps/lib")).unwrap();
+    let events = project.derpy_ok(&["--offline", "acquire"]);
+    assert!(!events.iter().any(|event| event["event"] == "mirror_update_failed"));
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    assert_eq!(project.locked_revision("lib"), locked);
+
+    let error = project.derpy_err(&["--offline", "upgrade", "--all"]);
+    assert_eq!(error["error"]["kind"], "MissingOffline");
+}
@@ -0,0 +1,89 @@
+//! Exercises the report comparing locked dependencies to the newest versions upstream.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::read_to_string;
+use std::path::Path;
+use serde_json::json;
+use common::{commit, create_repo, run, Project, GIT_ENVS};
+
+fn release(repo: &Path, version: &str) -> String {
+    commit(repo, "version.txt", version)
+}
+
+#[test]
+fn outdated_reports_newer_upstream_versions_without_changing_anything() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let branch_repo = project.scratch().join("branch_repo");
+    create_repo(&branch_repo, "untagged");
+    release(&branch_repo, "first");
+
+    let tagged_repo = project.scratch().join("tagged_repo");
+    create_repo(&tagged_repo, "untagged");
+    release(&tagged_repo, "1.0.0");
+    run(&tagged_repo, GIT_ENVS, "git", &["tag", "v1.0.0"]);
+
+    project.derpy_ok(&["add", "git", "branch_lib", branch_repo.to_str().unwrap()]);
+    project.derpy_ok(&["add", "git", "tagged_lib", tagged_repo.to_str().unwrap(), "--version", "^1.0"]);
+    project.derpy_ok(&["acquire"]);
+    let locked_branch = project.locked_revision("branch_lib");
+
+    let events = project.derpy_ok(&["outdated"]);
+    let branch_event = events.iter().find(|event| event["event"] == "outdated" && event["name"] == "branch_lib").unwrap();
+    assert_eq!(branch_event["latest_compatible"]["revision"], locked_branch.as_str());
+
+    let newest_branch = release(&branch_repo, "second");
+    let compatible = release(&tagged_repo, "1.1.0");
+    run(&tagged_repo, GIT_ENVS, "git", &["tag", "v1.1.0"]);
+    let newest = release(&tagged_repo, "2.0.0");
+    run(&tagged_repo, GIT_ENVS, "git", &["tag", "-a", "-m", "2.0.0", "v2.0.0"]);
+
+    let lock_before = read_to_string(project.root().join("derpy.lock.json")).unwrap();
+    let events = project.derpy_ok(&["outdated"]);
+
+    let branch_event = events.iter().find(|event| event["event"] == "outdated" && event["name"] == "branch_lib").unwrap();
+    assert_eq!(branch_event["locked_revision"], locked_branch.as_str());
+    assert_eq!(branch_event["latest_compatible"]["revision"], newest_branch.as_str());
+    assert_eq!(branch_event["latest"]["revision"], newest_branch.as_str());
+
+    let tagged_event = events.iter().find(|event| event["event"] == "outdated" && event["name"] == "tagged_lib").unwrap();
+    assert_eq!(tagged_event["locked_tag"], "v1.0.0");
+    assert_eq!(tagged_event["latest_compatible"]["tag"], "v1.1.0");
+    assert_eq!(tagged_event["latest_compatible"]["revision"], compatible.as_str());
+    assert_eq!(tagged_event["latest"]["tag"], "v2.0.0");
+    assert_eq!(tagged_event["latest"]["revision"], newest.as_str());
+
+    assert_eq!(read_to_string(project.root().join("derpy.lock.json")).unwrap(), lock_before);
+    assert_eq!(project.dependency_file("branch_lib", "version.txt"), "first");
+    assert_eq!(project.dependency_file("tagged_lib", "version.txt"), "1.0.0");
+}
+
+#[test]
+fn upstream_version_matches_the_exact_ref() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "untagged");
+    let old = release(&repo, "first");
+    let latest = release(&repo, "second");
+    // sorts after refs/heads/master, and ends in "master" too
+    run(&repo, GIT_ENVS, "git", &["branch", "old/master", &old]);
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    let events = project.derpy_ok(&["outdated"]);
+    let event = events.iter().find(|event| event["event"] == "outdated" && event["name"] == "lib").unwrap();
+    assert_eq!(event["latest"]["revision"], latest.as_str());
+
+    run(&repo, GIT_ENVS, "git", &["tag", "master", &old]);
+    let error = project.derpy_err(&["outdated"]);
+    assert_eq!(error["error"]["kind"], "AmbiguousRemoteVersion", "{}", error);
+    assert_eq!(error["error"]["refs"], json!(["refs/heads/master", "refs/tags/master"]));
+}
@@ -0,0 +1,131 @@
+//! Exercises `derpy.override.json` against repositories created in a temporary directory.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, remove_file, write};
+use std::path::Path;
+use serde_json::json;
+use common::{commit, create_repo, run, Project, GIT_ENVS};
+
+fn git_repo(project: &Project, name: &str, text: &str) -> (String, String) {
+    let repo = project.scratch().join(name);
+    let revision = create_repo(&repo, text);
+    (repo.to_str().unwrap().to_string(), revision)
+}
+
+#[test]
+fn path_override_leaves_lock_file_alone() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let (repo, revision) = git_repo(&project, "lib", "original");
+    project.derpy_ok(&["add", "git", "lib", &repo]);
+    project.derpy_ok(&["acquire"]);
+    let lock = read_to_string(project.root().join("derpy.lock.json")).unwrap();
+    assert_eq!(project.locked_revision("lib"), revision);
+
+    let dev = project.scratch().join("lib-dev");
+    create_dir_all(&dev).unwrap();
+    write(dev.join("a.txt"), "in development").unwrap();
+    write(project.root().join("derpy.override.json"), r#"{"overrides": {"lib": {"path": "../lib-dev"}}}"#).unwrap();
+
+    // the existing checkout might hold someone's work, so it isn't replaced
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "DependencyInTheWay", "{}", error);
+
+    remove_dir_all(project.root().join("deps/lib")).unwrap();
+    let events = project.derpy_ok(&["acquire"]);
+    assert!(events.iter().any(|event| event["event"] == "overridden" && event["name"] == "lib"));
+    assert_eq!(project.dependency_file("lib", "a.txt"), "in development");
+    assert_eq!(read_to_string(project.root().join("derpy.lock.json")).unwrap(), lock);
+
+    let events = project.derpy_ok(&["status"]);
+    let status = events.iter().find(|event| event["event"] == "status").unwrap();
+    assert_eq!(status["overridden"], true);
+
+    remove_file(project.root().join("derpy.override.json")).unwrap();
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "original");
+    assert_eq!(project.locked_revision("lib"), revision);
+}
+
+#[test]
+fn url_and_version_override() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let (repo, _) = git_repo(&project, "lib", "original");
+    let (fork, fork_revision) = git_repo(&project, "fork", "fork");
+    commit(Path::new(&fork), "a.txt", "newer fork");
+    project.derpy_ok(&["add", "git", "lib", &repo]);
+
+    let overrides = format!(r#"{{"overrides": {{"lib": {{"url": "{}", "version": "{}"}}}}}}"#, fork, fork_revision);
+    write(project.root().join("derpy.override.json"), overrides).unwrap();
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "fork");
+    assert!(!project.root().join("derpy.lock.json").exists());
+}
+
+#[test]
+fn version_override_runs_post_acquire_once() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let (repo, first) = git_repo(&project, "lib", "first");
+    commit(Path::new(&repo), "a.txt", "second");
+    project.derpy_ok(&["add", "git", "lib", &repo]);
+    let hook_log = project.scratch().join("hooks.log");
+    let hook = |name: &str| json!([["sh", "-c", "echo \"$1\" >> \"$2\"", "sh", format!("{} {{DEP_REVISION}}", name), hook_log.to_str().unwrap()]]);
+    project.edit_config(&|config| config["dependencies"]["lib"]["hooks"] = json!({
+        "post_acquire": hook("post_acquire"),
+        "post_upgrade": hook("post_upgrade"),
+    }));
+    write(project.root().join("derpy.override.json"), format!(r#"{{"overrides": {{"lib": {{"version": "{}"}}}}}}"#, first)).unwrap();
+
+    let events = project.derpy_ok(&["acquire", "--dry-run"]);
+    let plan = events.iter().find(|event| event["event"] == "planned").unwrap();
+    let actions = plan["actions"].as_array().unwrap().iter().map(|action| action["kind"].clone()).collect::<Vec<_>>();
+    assert_eq!(actions, [json!("acquire"), json!("upgrade"), json!("run_hook")]);
+    assert_eq!(plan["actions"][2]["hook"], "post_acquire");
+
+    let events = project.derpy_ok(&["acquire"]);
+    let acquired = events.iter().find(|event| event["event"] == "acquire").unwrap();
+    assert_eq!(acquired["outcome"], json!({ "kind": "acquired", "at_version": first }));
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    assert_eq!(read_to_string(&hook_log).unwrap(), format!("post_acquire {}\n", first));
+}
+
+#[test]
+fn url_override_replaces_existing_checkout() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let (repo, revision) = git_repo(&project, "lib", "original");
+    let (fork, _) = git_repo(&project, "fork", "fork");
+    run(Path::new(&fork), GIT_ENVS, "git", &["checkout", "-q", "-b", "my-fix"]);
+    commit(Path::new(&fork), "a.txt", "fixed");
+    project.derpy_ok(&["add", "git", "lib", &repo]);
+    project.derpy_ok(&["acquire"]);
+
+    let overrides = format!(r#"{{"overrides": {{"lib": {{"url": "{}", "version": "my-fix"}}}}}}"#, fork);
+    write(project.root().join("derpy.override.json"), overrides).unwrap();
+    let events = project.derpy_ok(&["acquire"]);
+    assert!(events.iter().any(|event| event["event"] == "checkout_replaced" && event["from_url"] == repo.as_str()));
+    assert_eq!(project.dependency_file("lib", "a.txt"), "fixed");
+
+    // local work in the fork's checkout isn't thrown away by going back to the original
+    remove_file(project.root().join("derpy.override.json")).unwrap();
+    write(project.root().join("deps/lib/a.txt"), "work in progress").unwrap();
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "MovedDependencyModified", "{}", error);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "work in progress");
+
+    run(&project.root().join("deps/lib"), GIT_ENVS, "git", &["checkout", "a.txt"]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "original");
+    assert_eq!(project.locked_revision("lib"), revision);
+}
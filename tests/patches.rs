@@ -0,0 +1,97 @@
+//! Exercises applying local patches on top of acquired dependencies.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{create_dir_all, read_to_string, write};
+use serde_json::{json, Value};
+use common::{commit, create_repo, edit_json, run, Project};
+
+const PATCH: &str = "--- a/b.txt\n+++ b/b.txt\n@@ -1 +1 @@\n-original\n+patched\n";
+
+#[test]
+fn patches_are_reapplied_when_the_locked_revision_changes() {
+    require_tool!("git", "--version");
+    require_tool!("patch", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "first\n");
+    commit(&repo, "b.txt", "original\n");
+
+    create_dir_all(project.root().join("patches")).unwrap();
+    write(project.root().join("patches/fix.patch"), PATCH).unwrap();
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    edit_json(&project.root().join("derpy.json"), &|config| {
+        config["dependencies"]["lib"]["patches"] = json!(["patches/fix.patch"]);
+    });
+
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "b.txt"), "patched\n");
+    let lock: Value = serde_json::from_str(&read_to_string(project.root().join("derpy.lock.json")).unwrap()).unwrap();
+    assert_eq!(lock["dependencies"]["lib"]["patches"][0]["path"], "patches/fix.patch");
+    assert_eq!(lock["dependencies"]["lib"]["patches"][0]["sha256"].as_str().unwrap().len(), 64);
+
+    // patches already applied aren't applied again
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "b.txt"), "patched\n");
+
+    let second = commit(&repo, "a.txt", "second\n");
+    run(&project.root().join("deps/lib"), &[], "git", &["fetch", "-q"]);
+    edit_json(&project.root().join("derpy.lock.json"), &|lock| {
+        lock["dependencies"]["lib"]["revision"] = json!(second);
+    });
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "second\n");
+    assert_eq!(project.dependency_file("lib", "b.txt"), "patched\n");
+
+    let third = commit(&repo, "b.txt", "rewritten\n");
+    run(&project.root().join("deps/lib"), &[], "git", &["fetch", "-q"]);
+    edit_json(&project.root().join("derpy.lock.json"), &|lock| {
+        lock["dependencies"]["lib"]["revision"] = json!(third);
+    });
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "PatchDoesNotApply", "{}", error);
+    assert!(error["error"]["message"].as_str().unwrap().contains("patches/fix.patch"));
+    assert_eq!(project.dependency_file("lib", "b.txt"), "rewritten\n");
+}
+
+#[test]
+fn patches_of_dependencies_are_found_in_the_dependency() {
+    require_tool!("git", "--version");
+    require_tool!("patch", "--version");
+
+    let project = Project::new();
+    let leaf = project.scratch().join("leaf");
+    create_repo(&leaf, "first\n");
+    commit(&leaf, "b.txt", "original\n");
+    let middle = project.scratch().join("middle");
+    create_repo(&middle, "middle\n");
+    commit(&middle, "patches/fix.patch", PATCH);
+    let config = json!({
+        "dependencies": {
+            "leaf": {
+                "name": "leaf",
+                "vcs": "git",
+                "url": leaf.to_str().unwrap(),
+                "version": "master",
+                "target": "deps/",
+                "options": {},
+                "patches": ["patches/fix.patch"],
+            },
+        },
+    });
+    commit(&middle, "derpy.json", &serde_json::to_string_pretty(&config).unwrap());
+
+    // a patch at the same path in the project itself isn't the one meant
+    create_dir_all(project.root().join("patches")).unwrap();
+    write(project.root().join("patches/fix.patch"), "not a patch").unwrap();
+    project.derpy_ok(&["add", "git", "middle", middle.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("leaf", "b.txt"), "patched\n");
+    let lock: Value = serde_json::from_str(&read_to_string(project.root().join("derpy.lock.json")).unwrap()).unwrap();
+    assert_eq!(lock["dependencies"]["leaf"]["patches"][0]["path"], "deps/middle/patches/fix.patch");
+}
@@ -0,0 +1,94 @@
+//! Exercises path dependencies against directories created in a temporary directory.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{create_dir_all, symlink_metadata, write};
+use common::{commit, create_repo, Project};
+
+#[test]
+fn symlinked_directory_is_locked_to_its_contents() {
+    let project = Project::new();
+    let lib = project.scratch().join("lib");
+    create_dir_all(&lib).unwrap();
+    write(lib.join("a.txt"), "first").unwrap();
+
+    project.derpy_ok(&["add", "path", "lib", "../lib"]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    let first_revision = project.locked_revision("lib");
+    assert_eq!(first_revision.len(), 64);
+    if cfg!(unix) {
+        assert!(symlink_metadata(project.root().join("deps/lib")).unwrap().file_type().is_symlink());
+    }
+
+    write(lib.join("a.txt"), "second").unwrap();
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "PathDependencyChanged", "{}", error);
+
+    project.derpy_ok(&["upgrade", "lib"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "second");
+    assert!(project.locked_revision("lib") != first_revision);
+}
+
+#[test]
+fn copied_git_repository_is_locked_to_its_revision() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let lib = project.scratch().join("lib");
+    let first = create_repo(&lib, "first");
+
+    project.derpy_ok(&["add", "path", "lib", lib.to_str().unwrap(), "--option", "method:copy"]);
+    project.derpy_ok(&["acquire"]);
+    assert!(!symlink_metadata(project.root().join("deps/lib")).unwrap().file_type().is_symlink());
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    assert_eq!(project.locked_revision("lib"), first);
+
+    let second = commit(&lib, "a.txt", "second");
+    project.derpy_ok(&["upgrade", "--all"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "second");
+    assert_eq!(project.locked_revision("lib"), second);
+}
+
+#[test]
+fn copy_only_replaces_its_own_directory() {
+    let project = Project::new();
+    let lib = project.scratch().join("lib");
+    create_dir_all(&lib).unwrap();
+    write(lib.join("a.txt"), "library").unwrap();
+    let existing = project.root().join("deps/lib");
+    create_dir_all(&existing).unwrap();
+    write(existing.join("a.txt"), "someone's work").unwrap();
+
+    project.derpy_ok(&["add", "path", "lib", lib.to_str().unwrap(), "--option", "method:copy"]);
+    project.derpy_ok(&["acquire"]);
+    let error = project.derpy_err(&["upgrade", "lib"]);
+    assert_eq!(error["error"]["kind"], "DependencyInTheWay", "{}", error);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "someone's work");
+}
+
+#[test]
+fn relative_path_of_a_dependency_is_relative_to_it() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let middle = project.scratch().join("middle");
+    create_repo(&middle, "middle");
+    commit(&middle, "vendored/leaf/a.txt", "leaf");
+    commit(&middle, "derpy.json", r#"{
+        "dependencies": {
+            "leaf": { "name": "leaf", "vcs": "path", "url": "vendored/leaf", "version": "", "target": "deps/", "options": {} }
+        }
+    }"#);
+
+    // a directory at the same path in the project itself isn't the one meant
+    create_dir_all(project.root().join("vendored/leaf")).unwrap();
+    write(project.root().join("vendored/leaf/a.txt"), "wrong").unwrap();
+    project.derpy_ok(&["add", "git", "middle", middle.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("leaf", "a.txt"), "leaf");
+}
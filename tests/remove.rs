@@ -0,0 +1,82 @@
+//! Exercises removing dependencies from a project, and deleting their checkouts along with them.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{read_to_string, write};
+use serde_json::Value;
+use common::{create_repo, Project};
+
+fn config(project: &Project) -> Value {
+    serde_json::from_str(&read_to_string(project.root().join("derpy.json")).unwrap()).unwrap()
+}
+
+fn lock(project: &Project) -> Value {
+    serde_json::from_str(&read_to_string(project.root().join("derpy.lock.json")).unwrap()).unwrap()
+}
+
+/// a project with `lib` acquired and locked
+fn acquired_project() -> Project {
+    let project = Project::new();
+    let repo = project.scratch().join("lib");
+    create_repo(&repo, "first");
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    project
+}
+
+#[test]
+fn remove_leaves_the_checkout_alone() {
+    require_tool!("git", "--version");
+
+    let project = acquired_project();
+    let events = project.derpy_ok(&["remove", "lib"]);
+    assert!(events.iter().any(|event| event["event"] == "removed" && event["name"] == "lib"), "{:?}", events);
+    assert!(config(&project)["dependencies"].get("lib").is_none());
+    assert!(lock(&project)["dependencies"].get("lib").is_none());
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+}
+
+#[test]
+fn remove_deletes_a_clean_checkout() {
+    require_tool!("git", "--version");
+
+    let project = acquired_project();
+    let events = project.derpy_ok(&["remove", "lib", "--delete"]);
+    assert!(events.iter().any(|event| event["event"] == "deleted"), "{:?}", events);
+    assert!(!project.root().join("deps/lib").exists());
+    assert!(config(&project)["dependencies"].get("lib").is_none());
+    assert!(lock(&project)["dependencies"].get("lib").is_none());
+}
+
+#[test]
+fn remove_refuses_to_delete_local_modifications() {
+    require_tool!("git", "--version");
+
+    let project = acquired_project();
+    write(project.root().join("deps/lib/a.txt"), "modified").unwrap();
+
+    let error = project.derpy_err(&["remove", "lib", "--delete"]);
+    assert_eq!(error["error"]["kind"], "DependencyModified", "{}", error);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "modified");
+    assert!(config(&project)["dependencies"].get("lib").is_some());
+    assert!(lock(&project)["dependencies"].get("lib").is_some());
+
+    project.derpy_ok(&["remove", "lib", "--delete", "--force"]);
+    assert!(!project.root().join("deps/lib").exists());
+    assert!(config(&project)["dependencies"].get("lib").is_none());
+}
+
+#[test]
+fn remove_unknown_dependency_is_an_error() {
+    let project = Project::new();
+    let before = read_to_string(project.root().join("derpy.json")).unwrap();
+
+    let error = project.derpy_err(&["remove", "nothing"]);
+    assert_eq!(error["error"]["kind"], "UnknownDependency", "{}", error);
+    assert_eq!(error["error"]["name"], "nothing");
+    assert_eq!(read_to_string(project.root().join("derpy.json")).unwrap(), before);
+}
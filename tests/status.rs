@@ -0,0 +1,64 @@
+//! Exercises `status`, which compares what is on disk with the lock file.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{create_dir_all, remove_dir_all, write};
+use serde_json::{json, Value};
+use common::{commit, create_repo, edit_json, run, Project};
+
+fn status(events: &[Value], name: &str) -> Value {
+    events.iter()
+        .find(|event| event["event"] == "status" && event["name"] == name)
+        .unwrap_or_else(|| panic!("no status for '{}' in {:?}", name, events))
+        .clone()
+}
+
+#[test]
+fn status_reports_each_state() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let mut revisions = Vec::new();
+    for name in ["locked", "other", "missing", "unlocked", "modified"].iter() {
+        let repo = project.scratch().join(name);
+        revisions.push(create_repo(&repo, name));
+        project.derpy_ok(&["add", "git", name, repo.to_str().unwrap()]);
+    }
+    project.derpy_ok(&["acquire"]);
+
+    let newer = commit(&project.scratch().join("other"), "a.txt", "newer");
+    let other = project.root().join("deps/other");
+    run(&other, &[], "git", &["fetch", "-q"]);
+    run(&other, &[], "git", &["checkout", "-q", &newer]);
+    remove_dir_all(project.root().join("deps/missing")).unwrap();
+    edit_json(&project.root().join("derpy.lock.json"), &|lock| {
+        lock["dependencies"].as_object_mut().unwrap().remove("unlocked");
+    });
+    write(project.root().join("deps/modified/a.txt"), "changed").unwrap();
+    create_dir_all(project.root().join("deps/stray")).unwrap();
+
+    let events = project.derpy_ok(&["status"]);
+    let locked = status(&events, "locked");
+    assert_eq!(locked["status"], json!({ "kind": "at_locked_version", "current_version": revisions[0] }));
+    assert_eq!(locked["local_modifications"], false);
+    assert_eq!(locked["changed_since_locked"], false);
+    assert_eq!(status(&events, "other")["status"], json!({
+        "kind": "at_other_version",
+        "current_version": newer,
+        "locked_version": revisions[1],
+    }));
+    assert_eq!(status(&events, "missing")["status"], json!({ "kind": "missing", "locked_version": revisions[2] }));
+    assert_eq!(status(&events, "missing")["local_modifications"], Value::Null);
+    assert_eq!(status(&events, "unlocked")["status"], json!({ "kind": "unlocked", "current_version": revisions[3] }));
+    let modified = status(&events, "modified");
+    assert_eq!(modified["status"]["kind"], "at_locked_version");
+    assert_eq!(modified["local_modifications"], true);
+
+    let unreferenced = events.iter().filter(|event| event["event"] == "unreferenced").collect::<Vec<_>>();
+    assert_eq!(unreferenced.len(), 1, "{:?}", events);
+    assert_eq!(unreferenced[0]["path"], "deps/stray");
+}
@@ -0,0 +1,62 @@
+//! Exercises dependencies that only want a subdirectory of their repository.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{create_dir_all, remove_dir_all, write};
+use common::{commit, create_repo, Project};
+
+#[test]
+fn git_subdirectory_is_placed_alone() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "top level");
+    commit(&repo, "lib/a.txt", "first");
+    let head = commit(&repo, "other/b.txt", "b");
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap(), "--subdir", "lib"]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    assert!(!project.root().join("deps/lib/other").exists());
+    assert!(!project.root().join("deps/lib/.git").exists());
+    assert!(!project.root().join(".derpy/checkouts/lib/other").exists());
+    assert_eq!(project.locked_revision("lib"), head);
+
+    // the copy is put back from the checkout it came from
+    remove_dir_all(project.root().join("deps/lib")).unwrap();
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+
+    project.derpy_ok(&["remove", "lib", "--delete", "--force"]);
+    assert!(!project.root().join("deps/lib").exists());
+    assert!(!project.root().join(".derpy/checkouts/lib").exists());
+}
+
+#[test]
+fn path_subdirectory_is_copied_into_place() {
+    let project = Project::new();
+    let lib = project.scratch().join("lib");
+    create_dir_all(lib.join("include")).unwrap();
+    write(lib.join("include/a.h"), "first").unwrap();
+    write(lib.join("b.txt"), "b").unwrap();
+
+    project.derpy_ok(&["add", "path", "lib", "../lib", "--subdir", "include"]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.h"), "first");
+    assert!(!project.root().join("deps/lib/b.txt").exists());
+
+    let events = project.derpy_ok(&["status"]);
+    assert!(events.iter().any(|event| event["event"] == "status" && event["status"]["kind"] == "at_locked_version"));
+}
+
+#[test]
+fn subdirectory_outside_repository_is_rejected() {
+    let project = Project::new();
+    let error = project.derpy_err(&["add", "git", "lib", "http://example.com/lib.git", "--subdir", "../elsewhere"]);
+    assert_eq!(error["error"]["kind"], "InvalidSubdir", "{}", error);
+}
@@ -0,0 +1,108 @@
+//! Exercises the stock VCS definitions against repositories created in a temporary directory.
+//! Tests for a version control system are skipped when its tools aren't installed.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{create_dir_all, write};
+use std::process::Command;
+use tempfile::TempDir;
+use common::{run, Project};
+
+#[test]
+fn hg() {
+    require_tool!("hg", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    let hg_envs = [("HGUSER", "test"), ("HGPLAIN", "1")];
+    create_dir_all(&repo).unwrap();
+    run(&repo, &hg_envs, "hg", &["init"]);
+    write(repo.join("a.txt"), "first").unwrap();
+    run(&repo, &hg_envs, "hg", &["commit", "--addremove", "-m", "first"]);
+
+    project.derpy_ok(&["add", "hg", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    let node = run(&repo, &hg_envs, "hg", &["log", "--rev", ".", "--template", "{node}"]);
+    assert_eq!(project.locked_revision("lib"), node);
+
+    write(repo.join("a.txt"), "second").unwrap();
+    run(&repo, &hg_envs, "hg", &["commit", "-m", "second"]);
+    project.derpy_ok(&["upgrade", "--all"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "second");
+    let node = run(&repo, &hg_envs, "hg", &["log", "--rev", ".", "--template", "{node}"]);
+    assert_eq!(project.locked_revision("lib"), node);
+}
+
+#[test]
+fn fossil() {
+    require_tool!("fossil", "version");
+
+    let project = Project::new().with_env("USER", "test");
+    let repo = project.scratch().join("repo.fossil");
+    let work = project.scratch().join("work");
+    let fossil_envs = [("USER", "test")];
+    run(&project.scratch(), &fossil_envs, "fossil", &["init", repo.to_str().unwrap(), "--admin-user", "test"]);
+    create_dir_all(&work).unwrap();
+    run(&work, &fossil_envs, "fossil", &["open", repo.to_str().unwrap()]);
+    write(work.join("a.txt"), "first").unwrap();
+    run(&work, &fossil_envs, "fossil", &["add", "a.txt"]);
+    run(&work, &fossil_envs, "fossil", &["commit", "-m", "first", "--no-warnings"]);
+
+    project.derpy_ok(&["add", "fossil", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    let first_revision = project.locked_revision("lib");
+    assert!(!first_revision.is_empty());
+
+    write(work.join("a.txt"), "second").unwrap();
+    run(&work, &fossil_envs, "fossil", &["commit", "-m", "second", "--no-warnings"]);
+    project.derpy_ok(&["upgrade", "--all"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "second");
+    assert!(project.locked_revision("lib") != first_revision);
+}
+
+#[test]
+fn p4() {
+    require_tool!("p4", "-V");
+    require_tool!("p4d", "-V");
+
+    // an rsh port runs a private server on demand, so no daemon needs to be left running
+    let scratch = TempDir::new().unwrap();
+    let server_root = scratch.path().join("server");
+    create_dir_all(&server_root).unwrap();
+    let port = format!("rsh:p4d -r {} -L log -i -J off", server_root.display());
+    let p4_envs = [("P4PORT", port.as_str()), ("P4USER", "test"), ("P4CLIENT", "setup")];
+
+    let work = scratch.path().join("work");
+    create_dir_all(&work).unwrap();
+    let spec = run(&work, &p4_envs, "p4", &["client", "-o", "setup"]);
+    let spec = spec.lines()
+        .map(|line| if line.starts_with("Root:") { format!("Root: {}", work.display()) } else { line.to_string() })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let spec_path = scratch.path().join("spec");
+    write(&spec_path, spec).unwrap();
+    let output = Command::new("p4").arg("client").arg("-i")
+        .envs(p4_envs.iter().cloned())
+        .current_dir(&work)
+        .stdin(std::fs::File::open(&spec_path).unwrap())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    write(work.join("a.txt"), "first").unwrap();
+    run(&work, &p4_envs, "p4", &["add", "a.txt"]);
+    run(&work, &p4_envs, "p4", &["submit", "-d", "first"]);
+
+    let project = Project::new()
+        .with_env("P4PORT", &port)
+        .with_env("P4USER", "test");
+    project.derpy_ok(&["add", "p4", "lib", "//depot/..."]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    assert_eq!(project.locked_revision("lib"), "1");
+}
@@ -0,0 +1,95 @@
+//! Exercises finding VCS definitions, both those on the search path and those built into derpy.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{copy, create_dir_all, read_to_string, remove_dir_all, write};
+use std::path::Path;
+use serde_json::{json, Value};
+use common::{create_repo, Project};
+
+fn definition(events: &[Value], name: &str) -> Value {
+    events.iter()
+        .find(|event| event["event"] == "vcs_definition" && event["name"] == name)
+        .unwrap_or_else(|| panic!("no definition of '{}' in {:?}", name, events))
+        .clone()
+}
+
+#[test]
+fn dump_prints_the_shipped_definition() {
+    let project = Project::new();
+    for name in ["git", "svn", "hg", "p4", "fossil"].iter() {
+        let events = project.derpy_ok(&["vcs", "dump", name]);
+        let shipped = read_to_string(format!("{}/vcs_info/{}.json", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+        assert_eq!(events, vec![json!({ "event": "built_in_vcs_definition", "name": name, "definition": shipped })]);
+    }
+
+    let error = project.derpy_err(&["vcs", "dump", "cvs"]);
+    assert_eq!(error["error"]["kind"], "NoBuiltInVcs", "{}", error);
+}
+
+#[test]
+fn built_in_definitions_are_used_when_none_are_found() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let events = project.derpy_ok(&["vcs", "list"]);
+    for name in ["archive", "fossil", "git", "hg", "p4", "path", "svn"].iter() {
+        let found = definition(&events, name);
+        assert_eq!(found["source"], json!({ "kind": "built_in" }), "{}", found);
+        assert_eq!(found["shadowed"], json!([]), "{}", found);
+    }
+
+    let repo = project.scratch().join("lib");
+    create_repo(&repo, "first");
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+}
+
+#[test]
+fn definitions_are_searched_for_in_order() {
+    let project = Project::new();
+    let install = project.scratch().join("install");
+    create_dir_all(&install).unwrap();
+    copy(env!("CARGO_BIN_EXE_derpy"), install.join("derpy")).unwrap();
+    let vcs_path = project.scratch().join("vcs_path");
+    let project = project
+        .with_exe(&install.join("derpy"))
+        .with_env("DERPY_VCS_PATH", vcs_path.to_str().unwrap());
+
+    // highest priority first
+    let levels = [
+        project.root().join(".derpy/vcs_info"),
+        project.scratch().join("config/derpy/vcs_info"),
+        vcs_path,
+        install.join("vcs_info"),
+    ];
+    let shipped = read_to_string(format!("{}/vcs_info/git.json", env!("CARGO_MANIFEST_DIR"))).unwrap();
+    let file_source = |level: &Path| json!({ "kind": "file", "path": level.join("git.json") });
+    project.derpy_ok(&["add", "git", "lib", "https://example.com/lib.git"]);
+
+    for winner in 0..levels.len() {
+        // only the winning definition works, so using any other would fail
+        for (index, level) in levels.iter().enumerate() {
+            if index < winner {
+                let _ = remove_dir_all(level);
+            } else {
+                create_dir_all(level).unwrap();
+                write(level.join("git.json"), if index == winner { &shipped[..] } else { "not a definition" }).unwrap();
+            }
+        }
+
+        let found = definition(&project.derpy_ok(&["vcs", "list"]), "git");
+        assert_eq!(found["source"], file_source(&levels[winner]));
+        let mut shadowed = levels[winner + 1..].iter().map(|level| file_source(level)).collect::<Vec<_>>();
+        shadowed.push(json!({ "kind": "built_in" }));
+        assert_eq!(found["shadowed"], json!(shadowed));
+
+        let events = project.derpy_ok(&["status"]);
+        assert!(events.iter().any(|event| event["event"] == "status" && event["name"] == "lib"), "{:?}", events);
+    }
+}
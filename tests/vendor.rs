@@ -0,0 +1,87 @@
+//! Exercises vendoring dependencies and restoring them from the vendored copy.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{copy, create_dir_all, read_link, symlink_metadata, write};
+use common::{create_repo, run, Project};
+
+#[test]
+fn vendored_git_dependency_restores_without_git() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "first");
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    let revision = project.locked_revision("lib");
+
+    write(project.root().join("deps/lib/a.txt"), "modified").unwrap();
+    let error = project.derpy_err(&["vendor", "vendor"]);
+    assert_eq!(error["error"]["kind"], "DependencyModified", "{}", error);
+    run(&project.root().join("deps/lib"), &[], "git", &["checkout", "a.txt"]);
+
+    let events = project.derpy_ok(&["vendor", "vendor"]);
+    assert!(events.iter().any(|event| event["event"] == "vendored" && event["revision"] == revision.as_str()));
+    let vendor_dir = project.root().join("vendor");
+    assert!(vendor_dir.join("lib/a.txt").is_file());
+    assert!(!vendor_dir.join("lib/.git").exists());
+    assert!(vendor_dir.join("derpy.vendor.json").is_file());
+
+    // a fresh copy of the project, as unpacked from a source release on a machine without git
+    let release = Project::new().with_env("PATH", "");
+    copy(project.root().join("derpy.json"), release.root().join("derpy.json")).unwrap();
+    copy(project.root().join("derpy.lock.json"), release.root().join("derpy.lock.json")).unwrap();
+    let vendored = vendor_dir.to_str().unwrap();
+    release.derpy_ok(&["acquire", "--vendored", vendored]);
+    assert_eq!(release.dependency_file("lib", "a.txt"), "first");
+    assert_eq!(release.locked_revision("lib"), revision);
+
+    let events = release.derpy_ok(&["acquire", "--vendored", vendored]);
+    assert!(events.iter().any(|event| event["event"] == "acquire" && event["outcome"]["kind"] == "no_change"));
+    let events = release.derpy_ok(&["status"]);
+    assert!(events.iter().any(|event| event["event"] == "status" && event["status"]["kind"] == "at_locked_version"));
+}
+
+#[test]
+fn vendor_dir_overlapping_a_dependency_is_refused() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "first");
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+
+    for dir in ["deps", "deps/lib", "deps/lib/vendor", "."].iter() {
+        let error = project.derpy_err(&["vendor", dir]);
+        assert_eq!(error["error"]["kind"], "VendorDirOverlapsDependency", "{}", error);
+    }
+    assert_eq!(project.dependency_file("lib", "a.txt"), "first");
+    assert!(!project.root().join("deps/lib/vendor").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn vendored_symlinks_stay_symlinks() {
+    let project = Project::new();
+    let lib = project.scratch().join("lib");
+    let docs = project.scratch().join("docs");
+    create_dir_all(&lib).unwrap();
+    create_dir_all(&docs).unwrap();
+    write(lib.join("a.txt"), "library").unwrap();
+    write(docs.join("index.txt"), "docs").unwrap();
+    ::std::os::unix::fs::symlink("../docs", lib.join("docs")).unwrap();
+
+    project.derpy_ok(&["add", "path", "lib", lib.to_str().unwrap()]);
+    project.derpy_ok(&["acquire"]);
+    project.derpy_ok(&["vendor", "vendor"]);
+    let vendored = project.root().join("vendor/lib/docs");
+    assert!(symlink_metadata(&vendored).unwrap().file_type().is_symlink());
+    assert_eq!(read_link(&vendored).unwrap().to_str().unwrap(), "../docs");
+}
@@ -0,0 +1,105 @@
+//! Exercises dependencies whose version is a semver range, resolved against their tags.
+
+extern crate serde_json;
+extern crate tempfile;
+
+#[macro_use]
+mod common;
+
+use std::fs::{read_to_string, remove_dir_all, write};
+use std::path::Path;
+use serde_json::{json, Value};
+use common::{commit, create_repo, run, Project, GIT_ENVS};
+
+fn tag_release(repo: &Path, tag: &str) {
+    commit(repo, "version.txt", tag);
+    run(repo, GIT_ENVS, "git", &["tag", tag]);
+}
+
+fn locked_tag(project: &Project, name: &str) -> Value {
+    let lock: Value = serde_json::from_str(&read_to_string(project.root().join("derpy.lock.json")).unwrap()).unwrap();
+    lock["dependencies"][name]["tag"].clone()
+}
+
+#[test]
+fn range_resolves_to_highest_matching_tag() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "untagged");
+    tag_release(&repo, "v1.3.0");
+    tag_release(&repo, "v1.4.1");
+    tag_release(&repo, "v1.5");
+    tag_release(&repo, "v2.0.0-beta.1");
+    tag_release(&repo, "not-a-version");
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap(), "--version", "^1.4"]);
+    let events = project.derpy_ok(&["acquire"]);
+    assert!(events.iter().any(|event| event["event"] == "version_resolved" && event["tag"] == "v1.5"));
+    assert_eq!(project.dependency_file("lib", "version.txt"), "v1.5");
+    assert_eq!(locked_tag(&project, "lib"), "v1.5");
+    let revision = project.locked_revision("lib");
+
+    // the lock file holds it at that tag, even once there is a newer match
+    tag_release(&repo, "v1.6.0");
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "version.txt"), "v1.5");
+    assert_eq!(project.locked_revision("lib"), revision);
+
+    // upgrading stays within the range unless breaking changes are allowed
+    tag_release(&repo, "v2.0.0");
+    project.derpy_ok(&["upgrade", "lib"]);
+    assert_eq!(project.dependency_file("lib", "version.txt"), "v1.6.0");
+    assert_eq!(locked_tag(&project, "lib"), "v1.6.0");
+
+    project.derpy_ok(&["upgrade", "lib", "--breaking"]);
+    assert_eq!(project.dependency_file("lib", "version.txt"), "v2.0.0");
+    assert_eq!(locked_tag(&project, "lib"), "v2.0.0");
+}
+
+#[test]
+fn range_without_a_match_is_an_error() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "untagged");
+    tag_release(&repo, "v1.0.0");
+
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap(), "--version", "^2"]);
+    let error = project.derpy_err(&["acquire"]);
+    assert_eq!(error["error"]["kind"], "NoMatchingVersion", "{}", error);
+    assert!(!project.root().join("deps/lib").exists());
+}
+
+#[test]
+fn fresh_range_checkout_goes_straight_to_the_tag() {
+    require_tool!("git", "--version");
+
+    let project = Project::new();
+    let repo = project.scratch().join("repo");
+    create_repo(&repo, "untagged");
+    tag_release(&repo, "v1.0.0");
+    commit(&repo, "version.txt", "unreleased");
+
+    let hook_log = project.scratch().join("hooks.log");
+    let hook = |name: &str| json!([["sh", "-c", "echo \"$1\" >> \"$2\"", "sh", format!("{} {{DEP_REVISION}}", name), hook_log.to_str().unwrap()]]);
+    project.derpy_ok(&["add", "git", "lib", repo.to_str().unwrap(), "--version", "^1"]);
+    project.edit_config(&|config| config["dependencies"]["lib"]["hooks"] = json!({
+        "post_acquire": hook("post_acquire"),
+        "post_upgrade": hook("post_upgrade"),
+    }));
+    project.derpy_ok(&["acquire"]);
+    let revision = project.locked_revision("lib");
+    assert_eq!(project.dependency_file("lib", "version.txt"), "v1.0.0");
+    assert_eq!(read_to_string(&hook_log).unwrap(), format!("post_acquire {}\n", revision));
+
+    // an overridden dependency that isn't there yet is acquired the same way
+    remove_dir_all(project.root().join("deps/lib")).unwrap();
+    write(&hook_log, "").unwrap();
+    write(project.root().join("derpy.override.json"), r#"{"overrides": {"lib": {"version": "~1.0"}}}"#).unwrap();
+    project.derpy_ok(&["acquire"]);
+    assert_eq!(project.dependency_file("lib", "version.txt"), "v1.0.0");
+    assert_eq!(read_to_string(&hook_log).unwrap(), format!("post_acquire {}\n", revision));
+}